@@ -0,0 +1,40 @@
+#!/bin/bash
+
+# Crashes while handling a command, counting the engine processes of a test in the file given
+# by CRASHFISH_MARKER. Process N crashes on the Nth command of the space separated
+# CRASHFISH_CRASHES (only "go" by default), processes past the end of the list don't crash.
+marker="${CRASHFISH_MARKER:?CRASHFISH_MARKER must be set}"
+run=$(( $(cat "$marker" 2>/dev/null || echo 0) + 1 ))
+echo "$run" > "$marker"
+read -r -a crashes <<< "${CRASHFISH_CRASHES:-go}"
+crash_on="${crashes[$((run - 1))]}"
+
+while read -r cmd; do
+    if [[ -n "$crash_on" && "$cmd" == "$crash_on"* ]]; then
+        echo "Segmentation fault" >&2
+        exit 139
+    fi
+    case "$cmd" in
+        uci)
+            echo "id name Crashfish"
+            echo "option name Hash type spin default 16 min 1 max 2048"
+            echo "uciok"
+            ;;
+        isready)
+            echo "readyok"
+            ;;
+        "go ponder"*)
+            echo "info depth 1 seldepth 1 multipv 1 score cp 59 nodes 56 time 1 pv d6f4 e3f4"
+            ;;
+        ponderhit)
+            echo "bestmove d6f4 ponder e3f4"
+            ;;
+        go*)
+            echo "info depth 1 seldepth 1 multipv 1 score cp 59 nodes 56 time 1 pv d6f4 e3f4"
+            echo "bestmove d6f4 ponder e3f4"
+            ;;
+        quit)
+            exit 0
+            ;;
+    esac
+done
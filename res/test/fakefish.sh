@@ -9,6 +9,18 @@ expect_cmd() {
     fi
 }
 
+search_info() {
+    echo "info string NNUE evaluation using nn-6877cd24400e.nnue enabled"
+    echo "info depth 1 seldepth 1 multipv 1 score cp 59 nodes 56 nps 56000 hashfull 0 tbhits 0 time 1 pv d6f4 e3f4"
+    if [[ $multipv -ge 2 ]]; then
+        echo "info depth 1 seldepth 1 multipv 2 score cp 12 nodes 56 nps 56000 hashfull 0 tbhits 0 time 1 pv a8b8 f4d6"
+    fi
+    echo "info depth 2 seldepth 2 multipv 1 score cp -27 nodes 227 nps 227000 hashfull 0 tbhits 0 time 1 pv a8b8 f4d6"
+    if [[ $multipv -ge 2 ]]; then
+        echo "info depth 2 seldepth 2 multipv 2 score cp -45 nodes 227 nps 227000 hashfull 0 tbhits 0 time 1 pv d6f4 e3f4"
+    fi
+}
+
 echo "fakefish test"
 expect_cmd uci
 echo "id name Stockfish 15"
@@ -36,6 +48,58 @@ echo "option name SyzygyProbeLimit type spin default 7 min 0 max 7"
 echo "option name Use NNUE type check default true"
 echo "option name EvalFile type string default nn-6877cd24400e.nnue"
 echo "uciok"
-expect_cmd isready
+
+# options may be set before the engine is asked if it is ready
+multipv=1
+while read -r cmd; do
+    case "$cmd" in
+        "setoption name MultiPV value "*)
+            multipv="${cmd##* }"
+            ;;
+        "setoption "*)
+            ;;
+        isready)
+            break
+            ;;
+        *)
+            echo "unexpected command: '$cmd' expected 'isready'"
+            exit 1
+            ;;
+    esac
+done
 echo "readyok"
 
+searching=0
+while read -r cmd; do
+    case "$cmd" in
+        "setoption name MultiPV value "*)
+            multipv="${cmd##* }"
+            ;;
+        isready)
+            echo "readyok"
+            ;;
+        "go infinite"*|"go ponder"*)
+            search_info
+            searching=1
+            ;;
+        ponderhit)
+            if [[ $searching == 1 ]]; then
+                echo "bestmove d6f4 ponder e3f4"
+                searching=0
+            fi
+            ;;
+        go*)
+            search_info
+            echo "bestmove a8b8 ponder f4d6"
+            ;;
+        stop)
+            if [[ $searching == 1 ]]; then
+                echo "bestmove a8b8 ponder f4d6"
+                searching=0
+            fi
+            ;;
+        quit)
+            exit 0
+            ;;
+    esac
+done
@@ -0,0 +1,144 @@
+use crate::engine::{BestMove, ChessEngine, Engine, Evaluation, Result};
+use crate::go::GoParams;
+use crate::parse::UCI;
+use crate::position::Position;
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tokio_stream::{wrappers::BroadcastStream, Stream};
+
+/// Infinite search on an engine, started with `Engine::analyze`.
+///
+/// The session is a stream of the latest evaluation of every line each time the engine sends
+/// the score of a line, which ends if the engine finishes the search on its own or exits. Updates are
+/// read from the engine state when consumed, so a slow consumer sees the newest evaluations.
+///
+/// ```no_run
+/// # async fn run() -> async_uci::engine::Result<()> {
+/// use async_uci::engine::{ChessEngine, Engine};
+/// use async_uci::go::GoParams;
+/// use async_uci::position::Position;
+/// use tokio_stream::StreamExt;
+///
+/// let mut engine = Engine::new("./stockfish").await?;
+/// engine.start_uci().await?;
+/// let mut session = engine.analyze(&Position::startpos(), GoParams::new()).await?;
+/// while let Some(lines) = session.next().await {
+///     if lines[0].depth >= 20 {
+///         break;
+///     }
+/// }
+/// let bm = session.stop().await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct AnalysisSession<'a> {
+    engine: &'a mut Engine,
+    params: GoParams,
+    events: BroadcastStream<UCI>,
+}
+
+impl<'a> AnalysisSession<'a> {
+    pub(crate) async fn start(
+        engine: &'a mut Engine,
+        position: &Position,
+        params: GoParams,
+    ) -> Result<AnalysisSession<'a>> {
+        let params = params.without_limits();
+        let events = engine.subscribe();
+        engine.set_position(position).await?;
+        engine.go(params.clone()).await?;
+        Ok(AnalysisSession {
+            engine,
+            params,
+            events,
+        })
+    }
+
+    /// Stop the search and wait for the best move found
+    pub async fn stop(self) -> Result<BestMove> {
+        let bm = self.engine.stop_search().await?;
+        self.engine.check_best_move(bm)
+    }
+
+    /// Stop the search and start analysing another position, discarding the best move of the
+    /// current search. Updates from the previous search aren't streamed after this returns. An
+    /// invalid position is rejected before stopping, leaving the current search running.
+    pub async fn set_position(&mut self, position: &Position) -> Result<()> {
+        self.engine.check_position(position)?;
+        self.engine.stop_search().await?;
+        // subscribing again drops anything still queued from the previous search
+        self.events = self.engine.subscribe();
+        self.engine.set_position(position).await?;
+        self.engine.go(self.params.clone()).await
+    }
+
+    /// Latest evaluation of every line, ranked by `multipv`
+    pub fn lines(&self) -> Vec<Evaluation> {
+        self.engine.lines()
+    }
+}
+
+impl Stream for AnalysisSession<'_> {
+    type Item = Vec<Evaluation>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let session = self.get_mut();
+        loop {
+            match Pin::new(&mut session.events).poll_next(cx) {
+                // only info with a score updates the evaluation of a line, the rest, like
+                // `info string` or `currmove` updates, would stream unchanged or empty lines
+                Poll::Ready(Some(Ok(UCI::Info(info))))
+                    if info.cp.is_some() || info.mate.is_some() =>
+                {
+                    return Poll::Ready(Some(session.engine.lines()))
+                }
+                Poll::Ready(Some(Ok(UCI::BestMove { .. }))) | Poll::Ready(None) => {
+                    return Poll::Ready(None)
+                }
+                // skipped updates are superseded by the next one anyway
+                Poll::Ready(Some(_)) => continue,
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::engine::{ChessEngine, Engine};
+    use crate::go::GoParams;
+    use crate::position::Position;
+    use tokio::time::Duration;
+    use tokio_stream::StreamExt;
+
+    #[tokio::test]
+    async fn test_analysis_session() -> anyhow::Result<()> {
+        let mut sf = Engine::new(test_file!("fakefish.sh")).await?;
+        sf.start_uci().await?;
+        let params = GoParams::new().depth(1).searchmoves(["a8b8"]);
+        let fen = "r2qk2r/pp3ppp/B1nbpn2/2pp1b2/Q2P1B2/2P1PN2/PP1N1PPP/R3K2R b KQkq - 4 8";
+        let mut session = sf.analyze(&Position::fen(fen), params).await?;
+        // fakefish sends an info string and two info lines, the last one reaching depth 2
+        let updates: Vec<_> = (&mut session).take(2).collect().await;
+        assert_eq!(updates[1][0].depth, 2);
+        // the info string isn't an update
+        let next = tokio::time::timeout(Duration::from_millis(100), session.next()).await;
+        assert!(next.is_err());
+
+        assert!(session
+            .set_position(&Position::fen(fen).moves(["e2e4"]))
+            .await
+            .is_err());
+        session
+            .set_position(&Position::fen(fen).moves(["d8a5", "a4a5"]))
+            .await?;
+        let updates: Vec<_> = (&mut session).take(2).collect().await;
+        assert_eq!(updates[1][0].depth, 2);
+        assert_eq!(session.lines(), updates[1]);
+        let bm = session.stop().await?;
+        assert_eq!(bm.bestmove, "a8b8");
+        Ok(())
+    }
+}
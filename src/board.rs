@@ -0,0 +1,584 @@
+use std::{fmt::Display, str::FromStr};
+use thiserror::Error;
+
+/// FEN of the standard chess starting position
+pub const STARTPOS_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+/// Side of the board
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Color {
+    White,
+    Black,
+}
+
+impl Color {
+    /// The other side of the board
+    pub fn opponent(self) -> Self {
+        match self {
+            Color::White => Color::Black,
+            Color::Black => Color::White,
+        }
+    }
+}
+
+/// Kind of a chess piece
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PieceKind {
+    Pawn,
+    Knight,
+    Bishop,
+    Rook,
+    Queen,
+    King,
+}
+
+impl PieceKind {
+    /// Lowercase letter of the piece in FEN and UCI promotions
+    pub fn to_char(self) -> char {
+        match self {
+            PieceKind::Pawn => 'p',
+            PieceKind::Knight => 'n',
+            PieceKind::Bishop => 'b',
+            PieceKind::Rook => 'r',
+            PieceKind::Queen => 'q',
+            PieceKind::King => 'k',
+        }
+    }
+
+    /// Piece kind of a letter, in any case
+    pub fn from_char(c: char) -> Option<Self> {
+        match c.to_ascii_lowercase() {
+            'p' => Some(PieceKind::Pawn),
+            'n' => Some(PieceKind::Knight),
+            'b' => Some(PieceKind::Bishop),
+            'r' => Some(PieceKind::Rook),
+            'q' => Some(PieceKind::Queen),
+            'k' => Some(PieceKind::King),
+            _ => None,
+        }
+    }
+
+    /// Material value in pawns, kings have none
+    pub fn value(self) -> u32 {
+        match self {
+            PieceKind::Pawn => 1,
+            PieceKind::Knight | PieceKind::Bishop => 3,
+            PieceKind::Rook => 5,
+            PieceKind::Queen => 9,
+            PieceKind::King => 0,
+        }
+    }
+}
+
+/// Chess piece of a side
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Piece {
+    pub color: Color,
+    pub kind: PieceKind,
+}
+
+impl Piece {
+    /// FEN letter of the piece, uppercase for white
+    pub fn to_char(self) -> char {
+        match self.color {
+            Color::White => self.kind.to_char().to_ascii_uppercase(),
+            Color::Black => self.kind.to_char(),
+        }
+    }
+
+    /// Piece of a FEN letter, uppercase for white
+    pub fn from_char(c: char) -> Option<Self> {
+        let color = if c.is_ascii_uppercase() {
+            Color::White
+        } else {
+            Color::Black
+        };
+        PieceKind::from_char(c).map(|kind| Piece { color, kind })
+    }
+}
+
+/// Square of the board, from a1 (0) to h8 (63) going through the files of each rank
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Square(pub(crate) u8);
+
+impl Square {
+    /// Square in a file and rank, both from 0 to 7
+    pub fn new(file: u8, rank: u8) -> Option<Self> {
+        if file < 8 && rank < 8 {
+            Some(Square(rank * 8 + file))
+        } else {
+            None
+        }
+    }
+
+    /// File of the square, from 0 (a) to 7 (h)
+    pub fn file(self) -> u8 {
+        self.0 % 8
+    }
+
+    /// Rank of the square, from 0 (1) to 7 (8)
+    pub fn rank(self) -> u8 {
+        self.0 / 8
+    }
+
+    /// Index of the square, from 0 (a1) to 63 (h8)
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl FromStr for Square {
+    type Err = ();
+
+    /// Parse a square in algebraic notation, i.e. `e4`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.as_bytes() {
+            [file @ b'a'..=b'h', rank @ b'1'..=b'8'] => {
+                Square::new(file - b'a', rank - b'1').ok_or(())
+            }
+            _ => Err(()),
+        }
+    }
+}
+
+impl Display for Square {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!(
+            "{}{}",
+            (b'a' + self.file()) as char,
+            self.rank() + 1
+        ))
+    }
+}
+
+/// Castling moves still available to each side
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct CastlingRights {
+    pub white_king: bool,
+    pub white_queen: bool,
+    pub black_king: bool,
+    pub black_queen: bool,
+}
+
+impl Display for CastlingRights {
+    /// Format as the castling field of a FEN, `-` when no side can castle
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let rights = [
+            (self.white_king, 'K'),
+            (self.white_queen, 'Q'),
+            (self.black_king, 'k'),
+            (self.black_queen, 'q'),
+        ];
+        let rights: String = rights.iter().filter(|(r, _)| *r).map(|(_, c)| c).collect();
+        if rights.is_empty() {
+            f.write_str("-")
+        } else {
+            f.write_str(&rights)
+        }
+    }
+}
+
+/// Errors produced parsing or validating a FEN
+#[derive(Error, Debug, PartialEq)]
+pub enum FenError {
+    /// A FEN has 6 fields, the clocks may be left out
+    #[error("expected 4 or 6 fields in FEN, found {0}")]
+    FieldCount(usize),
+
+    /// The piece placement field is malformed
+    #[error("invalid piece placement '{0}'")]
+    Placement(String),
+
+    /// The side to move isn't `w` or `b`
+    #[error("invalid side to move '{0}'")]
+    SideToMove(String),
+
+    /// The castling field is malformed or doesn't match the king and rook positions
+    #[error("invalid castling rights '{0}'")]
+    Castling(String),
+
+    /// The en passant square isn't behind a pawn that could have just moved two squares
+    #[error("invalid en passant square '{0}'")]
+    EnPassant(String),
+
+    /// A clock isn't a valid number
+    #[error("invalid {field} '{value}'")]
+    Clock { field: &'static str, value: String },
+
+    /// The position can't be reached in a game
+    #[error("illegal position: {0}")]
+    Illegal(String),
+}
+
+/// Chess position with everything needed to continue the game, parsed from and serialized
+/// to FEN.
+///
+/// ```
+/// use async_uci::board::{Board, Color};
+///
+/// let board: Board = "r2qk2r/pp3ppp/B1nbpn2/2pp1b2/Q2P1B2/2P1PN2/PP1N1PPP/R3K2R b KQkq - 4 8"
+///     .parse()
+///     .unwrap();
+/// assert_eq!(board.side_to_move(), Color::Black);
+/// assert!("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq e3 0 1".parse::<Board>().is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Board {
+    pub(crate) squares: [Option<Piece>; 64],
+    pub(crate) side_to_move: Color,
+    pub(crate) castling: CastlingRights,
+    pub(crate) en_passant: Option<Square>,
+    pub(crate) halfmove_clock: u32,
+    pub(crate) fullmove_number: u32,
+}
+
+impl Board {
+    /// Standard chess starting position
+    pub fn startpos() -> Self {
+        STARTPOS_FEN.parse().expect("invalid starting position")
+    }
+
+    /// Piece on a square, if any
+    pub fn piece_at(&self, square: Square) -> Option<Piece> {
+        self.squares[square.index()]
+    }
+
+    /// Side to play the next move
+    pub fn side_to_move(&self) -> Color {
+        self.side_to_move
+    }
+
+    /// Castling moves still available
+    pub fn castling(&self) -> CastlingRights {
+        self.castling
+    }
+
+    /// Square a pawn can be captured en passant on, if the last move was a double pawn push
+    pub fn en_passant(&self) -> Option<Square> {
+        self.en_passant
+    }
+
+    /// Plies since the last capture or pawn move, for the fifty-move rule
+    pub fn halfmove_clock(&self) -> u32 {
+        self.halfmove_clock
+    }
+
+    /// Number of the current move, starting at 1 and increasing after black moves
+    pub fn fullmove_number(&self) -> u32 {
+        self.fullmove_number
+    }
+
+    /// Material left on the board in pawns, counting both sides
+    pub fn material(&self) -> u32 {
+        self.squares.iter().flatten().map(|p| p.kind.value()).sum()
+    }
+
+    /// Square of the king of a side
+    pub fn king(&self, color: Color) -> Option<Square> {
+        let king = Piece {
+            color,
+            kind: PieceKind::King,
+        };
+        (0..64)
+            .map(Square)
+            .find(|sq| self.piece_at(*sq) == Some(king))
+    }
+
+    /// Parse the piece placement field, from rank 8 to rank 1
+    fn parse_placement(placement: &str) -> Result<[Option<Piece>; 64], FenError> {
+        let err = || FenError::Placement(placement.to_string());
+        let ranks: Vec<&str> = placement.split('/').collect();
+        if ranks.len() != 8 {
+            return Err(err());
+        }
+        let mut squares = [None; 64];
+        for (ix, rank) in ranks.iter().enumerate() {
+            let rank_ix = 7 - ix as u8;
+            let mut file = 0;
+            for c in rank.chars() {
+                match (c.to_digit(10), Piece::from_char(c)) {
+                    (Some(empty @ 1..=8), _) => file += empty as u8,
+                    (None, Some(piece)) => {
+                        let square = Square::new(file, rank_ix).ok_or_else(err)?;
+                        squares[square.index()] = Some(piece);
+                        file += 1;
+                    }
+                    _ => return Err(err()),
+                }
+                if file > 8 {
+                    return Err(err());
+                }
+            }
+            if file != 8 {
+                return Err(err());
+            }
+        }
+        Ok(squares)
+    }
+
+    /// Parse the castling field, every right must have its king and rook in place
+    fn parse_castling(&self, castling: &str) -> Result<CastlingRights, FenError> {
+        let err = || FenError::Castling(castling.to_string());
+        let mut rights = CastlingRights::default();
+        if castling == "-" {
+            return Ok(rights);
+        }
+        for c in castling.chars() {
+            let (right, king, rook) = match c {
+                'K' => (&mut rights.white_king, "e1", "h1"),
+                'Q' => (&mut rights.white_queen, "e1", "a1"),
+                'k' => (&mut rights.black_king, "e8", "h8"),
+                'q' => (&mut rights.black_queen, "e8", "a8"),
+                _ => return Err(err()),
+            };
+            if *right {
+                return Err(err());
+            }
+            *right = true;
+            let color = if c.is_ascii_uppercase() {
+                Color::White
+            } else {
+                Color::Black
+            };
+            let expected = [(king, PieceKind::King), (rook, PieceKind::Rook)];
+            for (square, kind) in expected {
+                let square = square.parse().expect("invalid castling square");
+                if self.piece_at(square) != Some(Piece { color, kind }) {
+                    return Err(err());
+                }
+            }
+        }
+        Ok(rights)
+    }
+
+    /// Parse the en passant field, there must be a pawn of the side that just moved in front
+    /// of the square and nothing behind it
+    fn parse_en_passant(&self, en_passant: &str) -> Result<Option<Square>, FenError> {
+        let err = || FenError::EnPassant(en_passant.to_string());
+        if en_passant == "-" {
+            return Ok(None);
+        }
+        let square: Square = en_passant.parse().map_err(|_| err())?;
+        let (rank, pushed_rank, from_rank) = match self.side_to_move {
+            Color::White => (5, 4, 6),
+            Color::Black => (2, 3, 1),
+        };
+        let pawn = Piece {
+            color: self.side_to_move.opponent(),
+            kind: PieceKind::Pawn,
+        };
+        let at = |rank| Square::new(square.file(), rank).expect("invalid square");
+        let valid = square.rank() == rank
+            && self.piece_at(at(pushed_rank)) == Some(pawn)
+            && self.piece_at(square).is_none()
+            && self.piece_at(at(from_rank)).is_none();
+        if valid {
+            Ok(Some(square))
+        } else {
+            Err(err())
+        }
+    }
+
+    /// Check the position can be reached in a game
+    fn validate(&self) -> Result<(), FenError> {
+        for color in [Color::White, Color::Black] {
+            let pieces = || self.squares.iter().flatten().filter(|p| p.color == color);
+            let kings = pieces().filter(|p| p.kind == PieceKind::King).count();
+            if kings != 1 {
+                return Err(FenError::Illegal(format!(
+                    "{:?} has {} kings",
+                    color, kings
+                )));
+            }
+            let pawns = pieces().filter(|p| p.kind == PieceKind::Pawn).count();
+            if pawns > 8 {
+                return Err(FenError::Illegal(format!(
+                    "{:?} has {} pawns",
+                    color, pawns
+                )));
+            }
+        }
+        let back_ranks = (0..8).chain(56..64).map(Square);
+        if back_ranks
+            .filter_map(|sq| self.piece_at(sq))
+            .any(|p| p.kind == PieceKind::Pawn)
+        {
+            return Err(FenError::Illegal(
+                "pawn on the first or last rank".to_string(),
+            ));
+        }
+        if self.in_check(self.side_to_move.opponent()) {
+            return Err(FenError::Illegal(
+                "the side not to move is in check".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Board {
+    type Err = FenError;
+
+    /// Parse and validate a FEN. The halfmove clock and fullmove number may be left out, as in
+    /// EPD, and default to 0 and 1.
+    fn from_str(fen: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        if fields.len() != 4 && fields.len() != 6 {
+            return Err(FenError::FieldCount(fields.len()));
+        }
+        let side_to_move = match fields[1] {
+            "w" => Color::White,
+            "b" => Color::Black,
+            side => return Err(FenError::SideToMove(side.to_string())),
+        };
+        let clock = |field: &'static str, value: Option<&&str>, default: u32| match value {
+            Some(value) => value.parse::<u32>().map_err(|_| FenError::Clock {
+                field,
+                value: value.to_string(),
+            }),
+            None => Ok(default),
+        };
+        let halfmove_clock = clock("halfmove clock", fields.get(4), 0)?;
+        let fullmove_number = clock("fullmove number", fields.get(5), 1)?;
+        if fullmove_number == 0 {
+            return Err(FenError::Clock {
+                field: "fullmove number",
+                value: "0".to_string(),
+            });
+        }
+        let mut board = Board {
+            squares: Self::parse_placement(fields[0])?,
+            side_to_move,
+            castling: CastlingRights::default(),
+            en_passant: None,
+            halfmove_clock,
+            fullmove_number,
+        };
+        board.validate()?;
+        board.castling = board.parse_castling(fields[2])?;
+        board.en_passant = board.parse_en_passant(fields[3])?;
+        Ok(board)
+    }
+}
+
+impl Display for Board {
+    /// Format as a FEN
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for rank in (0..8).rev() {
+            let mut empty = 0;
+            for file in 0..8 {
+                match self.squares[rank * 8 + file] {
+                    Some(piece) => {
+                        if empty > 0 {
+                            f.write_fmt(format_args!("{}", empty))?;
+                            empty = 0;
+                        }
+                        f.write_fmt(format_args!("{}", piece.to_char()))?;
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                f.write_fmt(format_args!("{}", empty))?;
+            }
+            if rank > 0 {
+                f.write_str("/")?;
+            }
+        }
+        let side = match self.side_to_move {
+            Color::White => "w",
+            Color::Black => "b",
+        };
+        let en_passant = match self.en_passant {
+            Some(square) => square.to_string(),
+            None => "-".to_string(),
+        };
+        f.write_fmt(format_args!(
+            " {} {} {} {} {}",
+            side, self.castling, en_passant, self.halfmove_clock, self.fullmove_number
+        ))
+    }
+}
+
+impl Default for Board {
+    fn default() -> Self {
+        Self::startpos()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::board::{Board, Color, FenError, Piece, PieceKind, Square, STARTPOS_FEN};
+
+    #[test]
+    fn test_fen_roundtrip() {
+        let fens = [
+            STARTPOS_FEN,
+            "r2qk2r/pp3ppp/B1nbpn2/2pp1b2/Q2P1B2/2P1PN2/PP1N1PPP/R3K2R b KQkq - 4 8",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "8/8/4k3/8/8/3K4/8/8 b - - 40 90",
+        ];
+        for fen in fens {
+            assert_eq!(fen.parse::<Board>().unwrap().to_string(), fen);
+        }
+        let board = Board::startpos();
+        assert_eq!(board.material(), 78);
+        assert_eq!(board.side_to_move(), Color::White);
+        assert_eq!(board.king(Color::Black), "e8".parse().ok());
+        assert_eq!(
+            board.piece_at("d1".parse::<Square>().unwrap()),
+            Some(Piece {
+                color: Color::White,
+                kind: PieceKind::Queen
+            })
+        );
+        let epd: Board = "8/8/4k3/8/8/3K4/8/8 w - -".parse().unwrap();
+        assert_eq!(epd.to_string(), "8/8/4k3/8/8/3K4/8/8 w - - 0 1");
+    }
+
+    #[test]
+    fn test_fen_errors() {
+        let parse = |fen: &str| fen.parse::<Board>().unwrap_err();
+        assert_eq!(parse("8/8/8 w - - 0 1 extra"), FenError::FieldCount(7));
+        assert!(matches!(
+            parse("rnbqkbnr/pppppppp/9/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"),
+            FenError::Placement(_)
+        ));
+        assert!(matches!(
+            parse("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBN w KQkq - 0 1"),
+            FenError::Placement(_)
+        ));
+        assert!(matches!(
+            parse("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR x KQkq - 0 1"),
+            FenError::SideToMove(_)
+        ));
+        assert!(matches!(
+            parse("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBN1 w KQkq - 0 1"),
+            FenError::Castling(_)
+        ));
+        assert!(matches!(
+            parse("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KKq - 0 1"),
+            FenError::Castling(_)
+        ));
+        assert!(matches!(
+            parse("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq e6 0 1"),
+            FenError::EnPassant(_)
+        ));
+        assert!(matches!(
+            parse("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - x 1"),
+            FenError::Clock { .. }
+        ));
+        assert!(matches!(
+            parse("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQ1BNR w kq - 0 1"),
+            FenError::Illegal(_)
+        ));
+        assert!(matches!(
+            parse("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNP w kq - 0 1"),
+            FenError::Illegal(_)
+        ));
+        assert!(matches!(
+            parse("4k2R/8/8/8/8/8/8/4K3 w - - 0 1"),
+            FenError::Illegal(_)
+        ));
+    }
+}
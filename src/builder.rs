@@ -0,0 +1,266 @@
+use crate::engine::{Engine, EngineError, Result};
+use crate::trace::Trace;
+use std::{ffi::OsString, path::PathBuf, process::Stdio};
+use tokio::{process::Command, time::Duration};
+
+/// What to do with the engine's stderr
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StderrMode {
+    /// Read stderr to report its last lines if the engine crashes, and to stream it through
+    /// `Engine::subscribe_stderr`
+    #[default]
+    Capture,
+    /// Write to the stderr of the current process, i.e. to log it along with ours
+    Inherit,
+    /// Discard everything the engine writes to stderr
+    Discard,
+}
+
+/// Configure how the engine process is spawned, and the engine once it is running.
+///
+/// ```no_run
+/// # async fn run() -> async_uci::engine::Result<()> {
+/// use async_uci::builder::EngineBuilder;
+/// use async_uci::engine::ChessEngine;
+///
+/// let mut engine = EngineBuilder::new("./stockfish")
+///     .current_dir("/opt/engines")
+///     .option("Threads", "4")
+///     .option("EvalFile", "nn-5af11540bbfe.nnue")
+///     .spawn()
+///     .await?;
+/// engine.start_uci().await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct EngineBuilder {
+    exe_path: String,
+    args: Vec<OsString>,
+    current_dir: Option<PathBuf>,
+    envs: Vec<(OsString, OsString)>,
+    stderr: StderrMode,
+    #[cfg(unix)]
+    nice: Option<i32>,
+    #[cfg(unix)]
+    own_process_group: bool,
+    options: Vec<(String, String)>,
+    timeout: Option<Duration>,
+    shutdown_timeout: Option<Duration>,
+    trace: Option<Trace>,
+}
+
+impl EngineBuilder {
+    /// Start configuring an engine executable, relative paths are resolved from the current
+    /// directory of this process
+    pub fn new(exe_path: &str) -> Self {
+        EngineBuilder {
+            exe_path: exe_path.to_string(),
+            args: Vec::new(),
+            current_dir: None,
+            envs: Vec::new(),
+            stderr: StderrMode::default(),
+            #[cfg(unix)]
+            nice: None,
+            #[cfg(unix)]
+            own_process_group: false,
+            options: Vec::new(),
+            timeout: None,
+            shutdown_timeout: None,
+            trace: None,
+        }
+    }
+
+    /// Add an argument to pass to the engine
+    pub fn arg(mut self, arg: impl Into<OsString>) -> Self {
+        self.args.push(arg.into());
+        self
+    }
+
+    /// Add a list of arguments to pass to the engine
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<OsString>,
+    {
+        self.args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// Working directory of the engine, where relative paths in options such as `EvalFile` or
+    /// `SyzygyPath` are resolved from
+    pub fn current_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.current_dir = Some(dir.into());
+        self
+    }
+
+    /// Set an environment variable for the engine, on top of the ones of the current process
+    pub fn env(mut self, key: impl Into<OsString>, value: impl Into<OsString>) -> Self {
+        self.envs.push((key.into(), value.into()));
+        self
+    }
+
+    /// Choose what to do with the engine's stderr, captured by default
+    pub fn stderr(mut self, mode: StderrMode) -> Self {
+        self.stderr = mode;
+        self
+    }
+
+    /// Scheduling priority of the engine process, from -20 (highest) to 19 (lowest). Raising
+    /// the priority above the one of the current process usually needs extra privileges.
+    #[cfg(unix)]
+    pub fn nice(mut self, nice: i32) -> Self {
+        self.nice = Some(nice);
+        self
+    }
+
+    /// Spawn the engine in its own process group, keeping it out of the terminal's one. Signals
+    /// such as the SIGINT of Ctrl-C then only reach the caller, which can still stop the search
+    /// and quit the engine, but the engine won't exit along with a caller killed by a signal.
+    #[cfg(unix)]
+    pub fn own_process_group(mut self, own: bool) -> Self {
+        self.own_process_group = own;
+        self
+    }
+
+    /// Set an option every time the UCI handshake is done in `start_uci`, before the engine
+    /// reports it is ready
+    pub fn option(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.options.push((name.into(), value.into()));
+        self
+    }
+
+    /// Set how long to wait for the engine to answer `uci` and `isready`
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Set how long `shutdown` waits for the engine to exit before killing it
+    pub fn shutdown_timeout(mut self, timeout: Duration) -> Self {
+        self.shutdown_timeout = Some(timeout);
+        self
+    }
+
+    /// Record every line exchanged with the engine, starting with the first line it sends
+    pub fn trace(mut self, trace: Trace) -> Self {
+        self.trace = Some(trace);
+        self
+    }
+
+    /// Spawn the engine process
+    pub async fn spawn(&self) -> Result<Engine> {
+        let proc = self
+            .command()
+            .spawn()
+            .map_err(|source| EngineError::Spawn {
+                path: self.exe_path.clone(),
+                source,
+            })?;
+        let mut engine = Engine::from_process(proc, self.options.clone(), self.trace.clone());
+        if let Some(timeout) = self.timeout {
+            engine.set_timeout(timeout);
+        }
+        if let Some(timeout) = self.shutdown_timeout {
+            engine.set_shutdown_timeout(timeout);
+        }
+        Ok(engine)
+    }
+
+    /// Build the command to spawn the engine with
+    fn command(&self) -> Command {
+        let mut cmd = Command::new(&self.exe_path);
+        cmd.args(&self.args);
+        cmd.envs(self.envs.iter().map(|(k, v)| (k, v)));
+        if let Some(dir) = &self.current_dir {
+            cmd.current_dir(dir);
+        }
+        cmd.stdin(Stdio::piped());
+        cmd.stdout(Stdio::piped());
+        cmd.stderr(match self.stderr {
+            StderrMode::Capture => Stdio::piped(),
+            StderrMode::Inherit => Stdio::inherit(),
+            StderrMode::Discard => Stdio::null(),
+        });
+        #[cfg(unix)]
+        if self.own_process_group {
+            cmd.process_group(0);
+        }
+        #[cfg(unix)]
+        if let Some(nice) = self.nice {
+            // SAFETY: setpriority is async-signal-safe and nothing is allocated in the closure
+            unsafe {
+                cmd.pre_exec(
+                    move || match libc::setpriority(libc::PRIO_PROCESS, 0, nice) {
+                        0 => Ok(()),
+                        _ => Err(std::io::Error::last_os_error()),
+                    },
+                );
+            }
+        }
+        cmd
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::builder::{EngineBuilder, StderrMode};
+    use crate::engine::{ChessEngine, EngineError};
+    use tokio::time::Duration;
+    use tokio_stream::StreamExt;
+
+    #[tokio::test]
+    async fn test_builder() -> anyhow::Result<()> {
+        let mut eng = EngineBuilder::new("sh")
+            .arg("-c")
+            .arg("echo \"$FAKEFISH_VAR $(pwd)\" >&2; exec ./fakefish.sh")
+            .current_dir(test_file!(""))
+            .env("FAKEFISH_VAR", "from-env")
+            .option("MultiPV", "2")
+            .timeout(Duration::from_secs(1))
+            .spawn()
+            .await?;
+        let mut stderr = eng.subscribe_stderr();
+        eng.start_uci().await?;
+        let line = stderr.next().await.expect("stderr closed")?;
+        assert!(line.starts_with("from-env "));
+        assert!(line.ends_with("/res/test"));
+        eng.go_depth(2).await?;
+        eng.best_move().await?;
+        assert_eq!(eng.get_lines().await.len(), 2);
+        eng.shutdown().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_builder_invalid_option() -> anyhow::Result<()> {
+        let mut eng = EngineBuilder::new(test_file!("fakefish.sh"))
+            .stderr(StderrMode::Discard)
+            .option("NoSuchOption", "1")
+            .spawn()
+            .await?;
+        assert!(matches!(
+            eng.start_uci().await,
+            Err(EngineError::InvalidOption(_))
+        ));
+        eng.shutdown().await?;
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_builder_nice() -> anyhow::Result<()> {
+        let mut eng = EngineBuilder::new("sh")
+            .args(["-c", "nice >&2; exec ./fakefish.sh"])
+            .current_dir(test_file!(""))
+            .nice(19)
+            .spawn()
+            .await?;
+        let mut stderr = eng.subscribe_stderr();
+        eng.start_uci().await?;
+        let line = stderr.next().await.expect("stderr closed")?;
+        assert_eq!(line.trim(), "19");
+        eng.shutdown().await?;
+        Ok(())
+    }
+}
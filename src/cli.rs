@@ -1,4 +1,4 @@
-use clap::{Args, Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 
 #[derive(Parser, Debug, Clone)]
 #[command(author, version, about, long_about)]
@@ -14,47 +14,147 @@ pub struct CLIArgs {
 pub struct GlobalArgs {
     #[clap(short = 'P', long)]
     pub engine_path: Option<String>,
+
+    /// Argument to pass to the engine, can be given multiple times.
+    /// i.e: '--engine-arg=--threads --engine-arg=4'
+    #[clap(long = "engine-arg", allow_hyphen_values = true)]
+    pub engine_args: Vec<String>,
+
+    /// Working directory for the engine, where relative paths in its options are resolved from
+    #[clap(long)]
+    pub engine_cwd: Option<String>,
+
+    /// Write every line exchanged with the engine to a file, with timestamps and direction
+    #[clap(long)]
+    pub trace: Option<String>,
 }
 
 #[derive(Subcommand, Debug, Clone)]
 pub enum Subcommands {
-    /// Search for moves in a position. If max_depth, max_time and mate_in are 0,
-    /// the engine will search until stopped.
-    Search {
-        /// FEN string of the position to search.
-        /// i.e: 'r2qk2r/pp3ppp/B1nbpn2/2pp1b2/Q2P1B2/2P1PN2/PP1N1PPP/R3K2R b KQkq - 4 8'
-        #[arg(short, long)]
-        fen: String,
-
-        /// Print moves along with evaluation.
-        #[arg(short = 'm', long)]
-        show_moves: bool,
-
-        /// Amount of lines to process, similar to setting `-O MultiPV=<n>`.
-        /// Note: Using `--lines 3 -O MultiPV=2` will make the engine calculate 2 lines, as -O takes
-        /// precedence over this option.
-        #[arg(short, long, default_value = "1")]
-        lines: usize,
-
-        /// Search up to a set depth.
-        #[arg(short = 'D', long, default_value = "0")]
-        max_depth: usize,
-
-        /// Search for a certain time in milliseconds.
-        #[arg(short = 'T', long, default_value = "0")]
-        max_time: usize,
-
-        /// Search for a mate in a certain number of moves.
-        #[arg(short = 'M', long, default_value = "0")]
-        mate_in: usize,
-
-        /// Specify options to pass to the engine. Can be used multiple times for multiple options.
-        /// i.e: '-O Hash=128 -O Threads=4'.
-        /// See 'list-options' for available options.
-        #[arg(short = 'O', long = "option")]
-        options: Vec<String>,
-    },
+    /// Search for moves in a position. If no limit (max_depth, max_time, mate_in, nodes or
+    /// clock times) is given, the engine will search until stopped with Ctrl-C.
+    Search(Box<SearchArgs>),
 
     /// List the available options for the current engine
     ListOptions {},
+
+    /// Expose the engine over a TCP or Unix socket speaking plain UCI, so that remote GUIs or
+    /// clients can use it.
+    Serve(ServeArgs),
+}
+
+#[derive(Args, Debug, Clone)]
+#[group(required = true, multiple = false, args = ["tcp", "unix"])]
+pub struct ServeArgs {
+    /// Address to listen for TCP connections on.
+    /// i.e: '127.0.0.1:9000'
+    #[arg(long)]
+    pub tcp: Option<String>,
+
+    /// Path of the Unix socket to listen for connections on.
+    #[arg(long)]
+    pub unix: Option<String>,
+
+    /// Amount of engine processes shared between connections. Connections wait for a free engine
+    /// when all of them are in use. When 0, a new engine is spawned for every connection.
+    #[arg(long, default_value = "0")]
+    pub pool: usize,
+}
+
+#[derive(Args, Debug, Clone)]
+pub struct SearchArgs {
+    /// FEN string of the position to search. Defaults to the standard starting position.
+    /// Shredder-FEN and X-FEN are accepted with '-O UCI_Chess960=true'.
+    /// i.e: 'r2qk2r/pp3ppp/B1nbpn2/2pp1b2/Q2P1B2/2P1PN2/PP1N1PPP/R3K2R b KQkq - 4 8'
+    #[arg(short, long)]
+    pub fen: Option<String>,
+
+    /// Moves played from the position given by --fen, in long algebraic notation.
+    /// i.e: '--moves e2e4 e7e5 g1f3'
+    #[arg(long, num_args = 1..)]
+    pub moves: Vec<String>,
+
+    /// Print moves along with evaluation.
+    #[arg(short = 'm', long)]
+    pub show_moves: bool,
+
+    /// Notation of the moves printed with --show-moves.
+    #[arg(long, value_enum, default_value_t = Notation::Uci)]
+    pub notation: Notation,
+
+    /// Amount of lines to process, similar to setting `-O MultiPV=<n>`.
+    /// Note: Using `--lines 3 -O MultiPV=2` will make the engine calculate 2 lines, as -O takes
+    /// precedence over this option.
+    #[arg(short, long, default_value = "1")]
+    pub lines: usize,
+
+    /// Search up to a set depth.
+    #[arg(short = 'D', long, default_value = "0")]
+    pub max_depth: usize,
+
+    /// Search for a certain time in milliseconds.
+    #[arg(short = 'T', long, default_value = "0")]
+    pub max_time: usize,
+
+    /// Search for a mate in a certain number of moves.
+    #[arg(short = 'M', long, default_value = "0")]
+    pub mate_in: usize,
+
+    /// Search up to a certain amount of nodes.
+    #[arg(long)]
+    pub nodes: Option<usize>,
+
+    /// Milliseconds white has left on the clock.
+    #[arg(long)]
+    pub wtime: Option<usize>,
+
+    /// Milliseconds black has left on the clock.
+    #[arg(long)]
+    pub btime: Option<usize>,
+
+    /// White increment per move in milliseconds.
+    #[arg(long)]
+    pub winc: Option<usize>,
+
+    /// Black increment per move in milliseconds.
+    #[arg(long)]
+    pub binc: Option<usize>,
+
+    /// Moves left until the next time control.
+    #[arg(long)]
+    pub movestogo: Option<usize>,
+
+    /// Restrict the search to these moves. Can be used multiple times.
+    /// i.e: '--searchmoves e2e4 --searchmoves d2d4'.
+    #[arg(long)]
+    pub searchmoves: Vec<String>,
+
+    /// Start the search in pondering mode, on the position after the last of --moves. Ctrl-C
+    /// sends ponderhit, after which the search goes on with its limits, or until Ctrl-C is
+    /// pressed again if there are none.
+    #[arg(long)]
+    pub ponder: bool,
+
+    /// Specify options to pass to the engine. Can be used multiple times for multiple options.
+    /// i.e: '-O Hash=128 -O Threads=4'.
+    /// See 'list-options' for available options.
+    #[arg(short = 'O', long = "option", value_parser = parse_option)]
+    pub options: Vec<(String, String)>,
+}
+
+/// Notation used to print moves
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum Notation {
+    /// Standard Algebraic Notation with move numbers, i.e. '8...Qa5 9.Qxa5 Nxa5'
+    San,
+    /// Long algebraic notation as sent by the engine, i.e. 'd8a5, a4a5, c6a5'
+    Uci,
+}
+
+/// Parse an engine option given as `NAME=VALUE`
+fn parse_option(option: &str) -> Result<(String, String), String> {
+    match option.split_once('=') {
+        Some((name, value)) => Ok((name.trim().to_string(), value.trim().to_string())),
+        None => Err(format!("expected NAME=VALUE, got '{}'", option)),
+    }
 }
@@ -1,15 +1,45 @@
-use crate::parse::{parse_uci, OptionType, UCI};
-use anyhow::{bail, Result};
+use crate::analysis::AnalysisSession;
+use crate::board::Board;
+use crate::builder::EngineBuilder;
+use crate::go::GoParams;
+use crate::moves::MoveError;
+use crate::parse::{parse_uci, CheckStatus, Info, OptionType, UCIError, Wdl, UCI};
+use crate::position::{Position, PositionError};
+use crate::score::{Bound, Score};
+use crate::trace::{Direction, Trace};
+use crate::wdl::WdlModel;
 use async_trait::async_trait;
 use std::{
+    collections::{BTreeMap, VecDeque},
     fmt::Display,
-    process::Stdio,
+    future::Future,
+    process::ExitStatus,
     sync::{Arc, Mutex},
 };
+use thiserror::Error;
 use tokio::{
-    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
-    process::{Child, ChildStdin, ChildStdout, Command},
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+    process::Child,
+    sync::{broadcast, watch},
+    task::JoinHandle,
+    time::Duration,
 };
+use tokio_stream::wrappers::BroadcastStream;
+
+/// Amount of messages kept for subscribers that fall behind
+const EVENTS_CAPACITY: usize = 1024;
+
+/// Time given to the engine to answer `uci` and `isready`
+const DEFAULT_RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Time given to the engine to exit after sending `quit`
+const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Amount of stderr lines kept to report engine crashes
+const STDERR_CAPACITY: usize = 20;
+
+/// Time to wait for a crashed engine to be reaped to report its exit status
+const CRASH_STATUS_TIMEOUT: Duration = Duration::from_secs(1);
 
 /// ChessEngine trait can be implemented for structures that implement the UCI Protocol
 #[async_trait]
@@ -17,11 +47,24 @@ pub trait ChessEngine {
     /// Start the UCI Protocol
     async fn start_uci(&mut self) -> Result<()>;
 
+    /// Register the engine with a name and code, waiting for the engine to accept them
+    async fn register(&mut self, name: &str, code: &str) -> Result<()>;
+
+    /// Tell the engine registration will be done later, required before using an engine that
+    /// reported a registration error
+    async fn register_later(&mut self) -> Result<()>;
+
     /// Notify engine of new game start
     async fn new_game(&mut self) -> Result<()>;
 
-    /// Notify engine of new position to search
-    async fn set_position(&mut self, position: &str) -> Result<()>;
+    /// Switch the engine's debug mode, in which it sends additional `info string` messages
+    async fn set_debug(&mut self, on: bool) -> Result<()>;
+
+    /// Notify engine of new position to search, failing if the position is malformed
+    async fn set_position(&mut self, position: &Position) -> Result<()>;
+
+    /// Notify engine to search for best move with any combination of `go` parameters
+    async fn go(&mut self, params: GoParams) -> Result<()>;
 
     /// Notify engine to search for best move until explicitly stopped
     async fn go_infinite(&mut self) -> Result<()>;
@@ -35,156 +78,656 @@ pub trait ChessEngine {
     /// Notify engine to search for a mate in a certain number of moves
     async fn go_mate(&mut self, mate_in: usize) -> Result<()>;
 
-    /// Notify engine to stop current search
+    /// Notify engine to stop current search and wait for it to send the best move found, which
+    /// can still be retrieved with `best_move`
     async fn stop(&mut self) -> Result<()>;
 
-    /// Retrieve the latest evaluation from the engine
+    /// Start pondering on the position reached after the expected reply `ponder_move` is played
+    /// in `position`. The search limits in `params` apply once the reply is confirmed with
+    /// `ponderhit`, until then the engine searches without limits and sends no best move.
+    async fn ponder(
+        &mut self,
+        position: &Position,
+        ponder_move: &str,
+        params: GoParams,
+    ) -> Result<()>;
+
+    /// Notify engine that the expected reply was played, the ponder search goes on as a normal
+    /// search and its result is retrieved with `best_move`
+    async fn ponderhit(&mut self) -> Result<()>;
+
+    /// Notify engine that another reply was played, stopping the ponder search and discarding
+    /// its result
+    async fn ponder_miss(&mut self) -> Result<()>;
+
+    /// Wait for the current search to finish and retrieve the best move found
+    async fn best_move(&mut self) -> Result<BestMove>;
+
+    /// Retrieve the latest evaluation of the best line from the engine
     async fn get_evaluation(&mut self) -> Option<Evaluation>;
 
+    /// Retrieve the latest evaluation of every line in the current search, ranked by `multipv`
+    async fn get_lines(&mut self) -> Vec<Evaluation>;
+
     /// Retrieve the list of available options from the engine
     async fn get_options(&mut self) -> Result<Vec<EngineOption>>;
 
-    /// Set an option in the engine
+    /// Set an option in the engine, parsing the value according to the option type
     async fn set_option(&mut self, option: String, value: String) -> Result<()>;
+
+    /// Set a `check` option in the engine
+    async fn set_check(&mut self, option: &str, value: bool) -> Result<()>;
+
+    /// Set a `spin` option in the engine, the value must be within the option bounds
+    async fn set_spin(&mut self, option: &str, value: isize) -> Result<()>;
+
+    /// Set a `combo` option in the engine, the value must be one of the option variants
+    async fn set_combo(&mut self, option: &str, value: &str) -> Result<()>;
+
+    /// Set a `string` option in the engine
+    async fn set_string(&mut self, option: &str, value: &str) -> Result<()>;
+
+    /// Press a `button` option in the engine
+    async fn press_button(&mut self, option: &str) -> Result<()>;
 }
 
-/// Engine can be created to spawn any Chess Engine that implements the UCI Protocol
+/// Input stream of the engine, where commands are written to
+type EngineWriter = Box<dyn AsyncWrite + Send + Unpin>;
+
+/// Output stream of the engine, where messages are read from
+type EngineReader = Box<dyn AsyncRead + Send + Unpin>;
+
+/// Engine can be created to spawn any Chess Engine that implements the UCI Protocol, or to talk
+/// UCI over any other transport such as sockets or in-memory pipes
 pub struct Engine {
-    stdin: ChildStdin,
+    stdin: EngineWriter,
     state: EngineState,
-    _proc: Child,
+    proc: Option<Child>,
+    reader: JoinHandle<()>,
+    options: Vec<(String, String)>,
+    /// Whether `UCI_Chess960` was enabled, positions aren't checked then
+    chess960: bool,
+    timeout: Duration,
+    shutdown_timeout: Duration,
 }
 
 impl Engine {
     pub async fn new(exe_path: &str) -> Result<Self> {
-        let (proc, stdin, stdout) = spawn_process(exe_path)?;
-        let state = EngineState::new(stdout).await;
-        Ok(Engine {
-            state: state,
-            stdin: stdin,
-            _proc: proc,
-        })
+        EngineBuilder::new(exe_path).spawn().await
     }
+
+    /// Start configuring an engine process, to pass it arguments, environment variables or
+    /// options to set on startup
+    pub fn builder(exe_path: &str) -> EngineBuilder {
+        EngineBuilder::new(exe_path)
+    }
+
+    /// Talk UCI through the stdin and stdout of a spawned process. The options are set every
+    /// time `start_uci` is called.
+    pub(crate) fn from_process(
+        mut proc: Child,
+        options: Vec<(String, String)>,
+        trace: Option<Trace>,
+    ) -> Self {
+        let stdin = proc.stdin.take().expect("no stdin available");
+        let stdout = proc.stdout.take().expect("no stdout available");
+        let stderr = proc
+            .stderr
+            .take()
+            .map(|stderr| Box::new(stderr) as EngineReader);
+        let (state, reader) = EngineState::new(Box::new(stdout), stderr, trace);
+        Engine {
+            state,
+            stdin: Box::new(stdin),
+            proc: Some(proc),
+            reader,
+            options,
+            chess960: false,
+            timeout: DEFAULT_RESPONSE_TIMEOUT,
+            shutdown_timeout: DEFAULT_SHUTDOWN_TIMEOUT,
+        }
+    }
+
+    /// Talk UCI through a reader and writer pair, i.e. the halves of a socket or in-memory pipe.
+    /// The engine is considered crashed once the reader is closed.
+    pub fn from_io<R, W>(reader: R, writer: W) -> Self
+    where
+        R: AsyncRead + Send + Unpin + 'static,
+        W: AsyncWrite + Send + Unpin + 'static,
+    {
+        let (state, reader) = EngineState::new(Box::new(reader), None, None);
+        Engine {
+            state,
+            stdin: Box::new(writer),
+            proc: None,
+            reader,
+            options: Vec::new(),
+            chess960: false,
+            timeout: DEFAULT_RESPONSE_TIMEOUT,
+            shutdown_timeout: DEFAULT_SHUTDOWN_TIMEOUT,
+        }
+    }
+
+    /// Talk UCI through a bidirectional stream, i.e. a `TcpStream` or `UnixStream`
+    pub fn from_stream<S>(stream: S) -> Self
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        let (reader, writer) = tokio::io::split(stream);
+        Self::from_io(reader, writer)
+    }
+
+    /// Set how long to wait for the engine to answer `uci` and `isready`. Engines loading big
+    /// files on startup (i.e. NNUE networks) may need more than the default 10 seconds.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// Set how long `shutdown` waits for the engine to exit before killing it
+    pub fn set_shutdown_timeout(&mut self, timeout: Duration) {
+        self.shutdown_timeout = timeout;
+    }
+
+    /// Ask the engine to quit and wait for the process to exit, killing it if it doesn't exit
+    /// within the shutdown timeout. Engines that aren't a child process have no exit status,
+    /// their transport is closed instead. Commands sent afterwards fail with `UnexpectedState`.
+    pub async fn shutdown(&mut self) -> Result<Option<ExitStatus>> {
+        self.state.state.send_replace(EngineStateEnum::Exited);
+        // the engine may have exited already, in which case there is no one to send quit to
+        self.state.record(Direction::Sent, "quit");
+        let _ = self.stdin.write_all(b"quit\n").await;
+        let _ = self.stdin.flush().await;
+        let status = match self.proc.as_mut() {
+            Some(proc) => match tokio::time::timeout(self.shutdown_timeout, proc.wait()).await {
+                Ok(status) => Some(status?),
+                Err(_) => {
+                    proc.kill().await?;
+                    Some(proc.wait().await?)
+                }
+            },
+            None => {
+                let _ = self.stdin.shutdown().await;
+                None
+            }
+        };
+        self.reader.abort();
+        Ok(status)
+    }
+
+    /// Engine identification as sent during `start_uci`
+    pub fn info(&self) -> EngineId {
+        self.state.id.lock().expect("couldn't acquire lock").clone()
+    }
+
+    /// Subscribe to every message sent by the engine from now on.
+    ///
+    /// Subscribers that fall more than a thousand messages behind will receive a `Lagged` error
+    /// reporting how many messages they missed.
+    pub fn subscribe(&self) -> BroadcastStream<UCI> {
+        BroadcastStream::new(self.state.events.subscribe())
+    }
+
+    /// Subscribe to every line sent by the engine from now on, as sent by the engine and without
+    /// the trailing newline. Lags the same way as `subscribe`.
+    pub fn subscribe_raw(&self) -> BroadcastStream<String> {
+        BroadcastStream::new(self.state.raw.subscribe())
+    }
+
+    /// Last registration status reported by the engine, if it requires registration. An engine
+    /// reporting an error after `start_uci` must be answered with `register` or `register_later`.
+    pub fn registration(&self) -> Option<CheckStatus> {
+        *self.state.registration.borrow()
+    }
+
+    /// Start analysing a position until stopped, only the `searchmoves` in `params` are kept as
+    /// the search is infinite. The session streams the evaluation updates and has to be stopped
+    /// to retrieve the best move, dropping it leaves the engine searching.
+    pub async fn analyze(
+        &mut self,
+        position: &Position,
+        params: GoParams,
+    ) -> Result<AnalysisSession<'_>> {
+        AnalysisSession::start(self, position, params).await
+    }
+
+    /// Record every line sent to and received from the engine from now on, or stop recording
+    /// with `None`
+    pub fn set_trace(&mut self, trace: Option<Trace>) {
+        *self.state.trace.lock().expect("couldn't acquire lock") = trace;
+    }
+
+    /// Subscribe to every line the engine writes to stderr from now on, without the trailing
+    /// newline. Nothing is received unless stderr is captured, which is the default for spawned
+    /// engines. Lags the same way as `subscribe`.
+    pub fn subscribe_stderr(&self) -> BroadcastStream<String> {
+        BroadcastStream::new(self.state.stderr_lines.subscribe())
+    }
+
+    /// Future resolving once the engine exits, whether it crashed or was shut down. It doesn't
+    /// borrow the engine, so it can be awaited while sending commands, i.e. to stop relaying
+    /// the engine when it dies.
+    pub fn exited(&self) -> impl Future<Output = ()> + Send + 'static {
+        let mut state = self.state.state.subscribe();
+        async move {
+            // the sender lives as long as the reader, which marks the engine as exited first
+            let _ = state.wait_for(|s| s.has_exited()).await;
+        }
+    }
+
+    /// Wait until the reader notices the engine crashed, for tests crashing it on purpose
+    #[cfg(test)]
+    pub(crate) async fn wait_crash(&self) {
+        let mut state = self.state.state.subscribe();
+        let _ = state.wait_for(|s| *s == EngineStateEnum::Crashed).await;
+    }
+
+    /// Send a line to the engine as is. The engine state isn't updated from raw commands, so
+    /// this is meant for relaying commands from another UCI client.
+    pub async fn send_raw(&mut self, line: &str) -> Result<()> {
+        self.send_command(format!("{}\n", line.trim_end())).await
+    }
+
     /// Send a command to the engine
     async fn send_command(&mut self, command: String) -> Result<()> {
-        self.stdin.write_all(command.as_bytes()).await?;
-        self.stdin.flush().await?;
-        Ok(())
+        let (crashed, shut_down) = {
+            let state = self.state.state.borrow();
+            (
+                *state == EngineStateEnum::Crashed,
+                *state == EngineStateEnum::Exited,
+            )
+        };
+        if crashed {
+            return Err(self.crash_error().await);
+        }
+        if shut_down {
+            return Err(EngineError::UnexpectedState(
+                "engine was shut down".to_string(),
+            ));
+        }
+        self.state.record(Direction::Sent, command.trim_end());
+        let res = self.stdin.write_all(command.as_bytes()).await;
+        let res = match res {
+            Ok(_) => self.stdin.flush().await,
+            Err(err) => Err(err),
+        };
+        match res {
+            Ok(_) => Ok(()),
+            Err(err) if err.kind() == std::io::ErrorKind::BrokenPipe => {
+                Err(self.crash_error().await)
+            }
+            Err(err) => Err(err.into()),
+        }
     }
 
-    /// Check if the expected state is the current engine state
-    async fn _expect_state(&mut self, exp_state: &EngineStateEnum) -> Result<()> {
-        let state = self.state.state.lock().expect("couldn't aquire state lock");
-        if *exp_state == *state {
-            return Ok(());
+    /// Build the error for an engine that exited unexpectedly, with its exit status if it can
+    /// be reaped shortly and the last lines it wrote to stderr
+    async fn crash_error(&mut self) -> EngineError {
+        let status = match self.proc.as_mut() {
+            Some(proc) => match tokio::time::timeout(CRASH_STATUS_TIMEOUT, proc.wait()).await {
+                Ok(Ok(status)) => Some(status),
+                _ => None,
+            },
+            None => None,
+        };
+        let stderr = self.state.stderr.lock().expect("couldn't acquire lock");
+        EngineError::Crashed {
+            status,
+            stderr: stderr.iter().cloned().collect(),
         }
-        bail!("engine didn't respond with {:?}", exp_state)
     }
 
-    /// Check if the expected state is the current engine state, retries a couple of times
-    /// waiting between attempts.
-    async fn expect_state(&mut self, exp_state: EngineStateEnum) -> Result<()> {
-        for _ in 0..10 {
-            match self._expect_state(&exp_state).await {
-                Ok(_) => return Ok(()),
-                Err(_) => tokio::time::sleep(std::time::Duration::from_millis(100)).await,
-            };
+    /// Wait until the engine reaches the expected state, failing if the engine exits or doesn't
+    /// get there within the configured timeout.
+    async fn expect_state(
+        &mut self,
+        exp_state: EngineStateEnum,
+        response: &'static str,
+    ) -> Result<()> {
+        let mut state = self.state.state.subscribe();
+        let waiting = state.wait_for(|s| *s == exp_state || s.has_exited());
+        let exited = match tokio::time::timeout(self.timeout, waiting).await {
+            Ok(state) => state.map(|s| s.has_exited()).unwrap_or(true),
+            Err(_) => {
+                return Err(EngineError::Timeout {
+                    response,
+                    timeout: self.timeout,
+                })
+            }
+        };
+        if exited {
+            Err(self.crash_error().await)
+        } else {
+            Ok(())
         }
-        bail!("engine didn't respond with {:?}", exp_state)
     }
 
-    /// Check if the engine initialized UCI
-    async fn expect_uciok(&mut self) -> Result<()> {
-        self.expect_state(EngineStateEnum::Initialized).await
+    /// Send `uci` and wait for the engine to initialize UCI
+    async fn uci(&mut self) -> Result<()> {
+        self.set_state(EngineStateEnum::Uninitialized);
+        self.send_command("uci\n".to_string()).await?;
+        self.expect_state(EngineStateEnum::Initialized, "uciok")
+            .await
+    }
+
+    /// Send `isready` and wait for the engine to be ready to receive commands
+    async fn isready(&mut self) -> Result<()> {
+        self.set_state(EngineStateEnum::Initialized);
+        self.send_command("isready\n".to_string()).await?;
+        self.expect_state(EngineStateEnum::Ready, "readyok").await
+    }
+
+    /// Change current engine state, an exited engine stays exited
+    fn set_state(&mut self, new_state: EngineStateEnum) {
+        self.state.set_state(new_state);
     }
 
-    /// Check if the engine is ready to receive commands
-    async fn expect_readyok(&mut self) -> Result<()> {
-        self.expect_state(EngineStateEnum::Ready).await
+    /// Check the engine is running a ponder search, reporting a crash if it exited
+    async fn expect_pondering(&mut self) -> Result<()> {
+        let (pondering, exited) = {
+            let state = self.state.state.borrow();
+            (*state == EngineStateEnum::Pondering, state.has_exited())
+        };
+        match (pondering, exited) {
+            (true, _) => Ok(()),
+            (false, true) => Err(self.crash_error().await),
+            (false, false) => Err(EngineError::UnexpectedState(
+                "engine is not pondering".to_string(),
+            )),
+        }
+    }
+
+    /// Find an option advertised by the engine, names are case insensitive
+    fn find_option(&self, name: &str) -> Result<EngineOption> {
+        let options = self.state.options.lock().expect("couldn't acquire lock");
+        match options.iter().find(|o| o.name.eq_ignore_ascii_case(name)) {
+            Some(opt) => Ok(opt.clone()),
+            None => Err(OptionError::Unknown(name.to_string()).into()),
+        }
     }
 
-    /// Change current engine state
-    async fn set_state(&mut self, new_state: EngineStateEnum) -> Result<()> {
-        // TODO: Return old state
-        let mut state = self.state.state.lock().expect("couldn't acquire lock");
-        *state = new_state;
+    /// Send a `setoption` command, buttons have no value
+    async fn send_option(&mut self, name: &str, value: Option<String>) -> Result<()> {
+        let cmd = match &value {
+            Some(value) => format!("setoption name {} value {}\n", name, value),
+            None => format!("setoption name {}\n", name),
+        };
+        self.send_command(cmd).await?;
+        if name.eq_ignore_ascii_case("UCI_Chess960") {
+            self.chess960 = value.is_some_and(|v| v.eq_ignore_ascii_case("true"));
+        }
         Ok(())
     }
+
+    /// Check a position can be set, returning the board it leads to. Chess960 castling isn't
+    /// supported, so positions aren't checked when it's enabled and there is no board.
+    pub(crate) fn check_position(&self, position: &Position) -> Result<Option<Board>> {
+        if self.chess960 {
+            Ok(None)
+        } else {
+            Ok(Some(position.board()?))
+        }
+    }
+
+    /// Stop the current search and wait for the engine to send the best move found so far,
+    /// which isn't checked as callers may discard it
+    pub(crate) async fn stop_search(&mut self) -> Result<BestMove> {
+        self.send_command("stop\n".to_string()).await?;
+        match tokio::time::timeout(self.timeout, self.search_result()).await {
+            Ok(Some(bm)) => Ok(bm),
+            Ok(None) => Err(self.crash_error().await),
+            Err(_) => Err(EngineError::Timeout {
+                response: "bestmove",
+                timeout: self.timeout,
+            }),
+        }
+    }
+
+    /// Wait for the best move of the current search, `None` if the engine exits first. The
+    /// state is published after the move, so it's awaited too for the next command to find
+    /// the search over.
+    async fn search_result(&mut self) -> Option<BestMove> {
+        let mut rx = self.state.best_move.subscribe();
+        let mut state = self.state.state.subscribe();
+        let bm = tokio::select! {
+            // the sender is owned by the engine state, so it is never dropped while waiting
+            bm = rx.wait_for(|bm| bm.is_some()) => bm.ok().and_then(|bm| bm.clone()),
+            _ = state.wait_for(|s| s.has_exited()) => None,
+        };
+        let _ = state.wait_for(|s| !s.is_searching()).await;
+        bm
+    }
+
+    /// Check the best move can be played in the position searched, engines answer `(none)` or
+    /// `0000` when there is no legal move
+    pub(crate) fn check_best_move(&self, bm: BestMove) -> Result<BestMove> {
+        let board = match self.board() {
+            Some(board) => board,
+            None => return Ok(bm),
+        };
+        match bm.bestmove.as_str() {
+            "(none)" | "0000" if board.legal_moves().is_empty() => Ok(bm),
+            mv => match board.parse_move(mv) {
+                Ok(_) => Ok(bm),
+                Err(err) => Err(EngineError::IllegalBestMove(err)),
+            },
+        }
+    }
+
+    /// Board of the last position set, to check moves against
+    fn board(&self) -> Option<Board> {
+        self.state
+            .board
+            .lock()
+            .expect("couldn't acquire lock")
+            .clone()
+    }
+
+    /// Latest evaluation of every line in the current search
+    pub(crate) fn lines(&self) -> Vec<Evaluation> {
+        let evs = self
+            .state
+            .evaluations
+            .lock()
+            .expect("couldn't acquire lock");
+        evs.values().cloned().collect()
+    }
+
+    /// Start a search with the given `go` command, discarding the result of any previous search
+    async fn start_search(&mut self, command: String, state: EngineStateEnum) -> Result<()> {
+        self.state.best_move.send_replace(None);
+        self.state
+            .evaluations
+            .lock()
+            .expect("couldn't acquire lock")
+            .clear();
+        self.set_state(state);
+        self.send_command(command).await
+    }
 }
 
-/// Spawn a subprocess and return handles for stdin and stdout
-fn spawn_process(exe_path: &str) -> Result<(Child, ChildStdin, ChildStdout)> {
-    let mut cmd = Command::new(exe_path);
-    cmd.stdin(Stdio::piped());
-    cmd.stdout(Stdio::piped());
-    let mut proc = cmd.spawn()?;
-    let stdout = proc.stdout.take().expect("no stdout available");
-    let stdin = proc.stdin.take().expect("no stdin available");
-    Ok((proc, stdin, stdout))
+impl Drop for Engine {
+    /// Kill the engine if it wasn't shut down, the runtime reaps the process in the background
+    fn drop(&mut self) {
+        self.reader.abort();
+        if let Some(proc) = self.proc.as_mut() {
+            let _ = proc.start_kill();
+        }
+    }
 }
 
 #[async_trait]
 impl ChessEngine for Engine {
     async fn start_uci(&mut self) -> Result<()> {
-        self.send_command("uci\n".to_string()).await?;
-        self.expect_uciok().await?;
-        self.send_command("isready\n".to_string()).await?;
-        self.expect_readyok().await?;
-        Ok(())
+        self.uci().await?;
+        for (name, value) in self.options.clone() {
+            self.set_option(name, value).await?;
+        }
+        self.isready().await?;
+        // the check is done before answering isready, so its result is known by now
+        match *self.state.copyprotection.borrow() {
+            Some(CheckStatus::Error) => Err(EngineError::CopyProtection),
+            _ => Ok(()),
+        }
+    }
+
+    async fn register(&mut self, name: &str, code: &str) -> Result<()> {
+        self.state.registration.send_replace(None);
+        self.send_command(format!("register name {} code {}\n", name, code))
+            .await?;
+        let mut registration = self.state.registration.subscribe();
+        let mut state = self.state.state.subscribe();
+        // the engine reports it is checking before sending the result
+        let checked =
+            |s: &Option<CheckStatus>| matches!(s, Some(CheckStatus::Ok | CheckStatus::Error));
+        let waiting = async {
+            tokio::select! {
+                status = registration.wait_for(checked) => status.ok().and_then(|s| *s),
+                _ = state.wait_for(|s| s.has_exited()) => None,
+            }
+        };
+        match tokio::time::timeout(self.timeout, waiting).await {
+            Ok(Some(CheckStatus::Ok)) => Ok(()),
+            Ok(Some(_)) => Err(EngineError::Registration),
+            Ok(None) => Err(self.crash_error().await),
+            Err(_) => Err(EngineError::Timeout {
+                response: "registration",
+                timeout: self.timeout,
+            }),
+        }
+    }
+
+    async fn register_later(&mut self) -> Result<()> {
+        self.send_command("register later\n".to_string()).await
     }
 
     async fn new_game(&mut self) -> Result<()> {
         self.send_command("ucinewgame\n".to_string()).await?;
-        self.set_state(EngineStateEnum::Initialized).await?;
-        self.send_command("isready\n".to_string()).await?;
-        self.expect_readyok().await?;
+        self.isready().await
+    }
+
+    async fn set_debug(&mut self, on: bool) -> Result<()> {
+        if on {
+            self.send_command("debug on\n".to_string()).await
+        } else {
+            self.send_command("debug off\n".to_string()).await
+        }
+    }
+
+    async fn set_position(&mut self, position: &Position) -> Result<()> {
+        let board = self.check_position(position)?;
+        self.send_command(format!("{}\n", position)).await?;
+        *self.state.board.lock().expect("couldn't acquire lock") = board;
         Ok(())
     }
 
-    async fn set_position(&mut self, fen: &str) -> Result<()> {
-        let cmd = format!("position fen {}\n", fen);
-        self.send_command(cmd.to_string()).await
+    async fn go(&mut self, params: GoParams) -> Result<()> {
+        if let Some(board) = self.board() {
+            for mv in params.searched_moves() {
+                board.parse_move(mv)?;
+            }
+        }
+        let state = if params.is_ponder() {
+            EngineStateEnum::Pondering
+        } else {
+            EngineStateEnum::Thinking
+        };
+        self.start_search(format!("{}\n", params), state).await
     }
 
     async fn go_infinite(&mut self) -> Result<()> {
-        self.send_command("go infinite\n".to_string()).await?;
-        self.set_state(EngineStateEnum::Thinking).await?;
-        Ok(())
+        self.go(GoParams::new().infinite()).await
     }
 
     async fn go_depth(&mut self, depth: usize) -> Result<()> {
-        self.send_command(format!("go depth {}\n", depth).to_string())
-            .await?;
-        self.set_state(EngineStateEnum::Thinking).await?;
-        Ok(())
+        self.go(GoParams::new().depth(depth)).await
     }
 
     async fn go_time(&mut self, ms: usize) -> Result<()> {
-        self.send_command(format!("go movetime {}\n", ms).to_string())
-            .await?;
-        self.set_state(EngineStateEnum::Thinking).await?;
-        Ok(())
+        self.go(GoParams::new().movetime(ms)).await
     }
 
     async fn go_mate(&mut self, mate_in: usize) -> Result<()> {
-        self.send_command(format!("go mate {}\n", mate_in).to_string())
-            .await?;
-        self.set_state(EngineStateEnum::Thinking).await?;
-        Ok(())
+        self.go(GoParams::new().mate(mate_in)).await
     }
 
     async fn stop(&mut self) -> Result<()> {
-        self.send_command("stop\n".to_string()).await?;
-        self.set_state(EngineStateEnum::Initialized).await?;
+        let searching = self.state.state.borrow().is_searching();
+        // waiting for the best move keeps it from being taken as the result of the next search
+        if searching {
+            self.stop_search().await.map(|_| ())
+        } else {
+            self.send_command("stop\n".to_string()).await
+        }
+    }
+
+    async fn ponder(
+        &mut self,
+        position: &Position,
+        ponder_move: &str,
+        params: GoParams,
+    ) -> Result<()> {
+        let mut position = position.clone();
+        position.push(ponder_move);
+        self.set_position(&position).await?;
+        self.go(params.ponder()).await
+    }
+
+    async fn ponderhit(&mut self) -> Result<()> {
+        self.expect_pondering().await?;
+        self.set_state(EngineStateEnum::Thinking);
+        self.send_command("ponderhit\n".to_string()).await
+    }
+
+    async fn ponder_miss(&mut self) -> Result<()> {
+        self.expect_pondering().await?;
+        // the engine answers stop with the best move of the ponder search, which is of no use
+        self.stop_search().await?;
+        self.state.best_move.send_replace(None);
+        self.state
+            .evaluations
+            .lock()
+            .expect("couldn't acquire lock")
+            .clear();
         Ok(())
     }
 
+    async fn best_move(&mut self) -> Result<BestMove> {
+        let rx = self.state.best_move.subscribe();
+        let state = self.state.state.subscribe();
+        // a search lost to a crash must be reported as such, so that it can be restarted
+        let exited = state.borrow().has_exited();
+        if rx.borrow().is_none() && exited {
+            return Err(self.crash_error().await);
+        }
+        if *state.borrow() == EngineStateEnum::Pondering {
+            return Err(EngineError::UnexpectedState(
+                "engine is pondering, call ponderhit or ponder_miss first".to_string(),
+            ));
+        }
+        // the state is read before the move, which is published first when a search ends
+        let searching = *state.borrow() == EngineStateEnum::Thinking;
+        if !searching && rx.borrow().is_none() {
+            return Err(EngineError::UnexpectedState(
+                "engine is not searching".to_string(),
+            ));
+        }
+        match self.search_result().await {
+            Some(bm) => self.check_best_move(bm),
+            None => Err(self.crash_error().await),
+        }
+    }
+
     async fn get_evaluation(&mut self) -> Option<Evaluation> {
-        let ev = self.state.evaluation.lock().expect("couldn't acquire lock");
-        return match &*ev {
-            Some(e) => Some(e.clone()),
-            None => None,
-        };
+        let evs = self
+            .state
+            .evaluations
+            .lock()
+            .expect("couldn't acquire lock");
+        evs.values().next().cloned()
+    }
+
+    async fn get_lines(&mut self) -> Vec<Evaluation> {
+        self.lines()
     }
 
     async fn get_options(&mut self) -> Result<Vec<EngineOption>> {
@@ -193,54 +736,266 @@ impl ChessEngine for Engine {
     }
 
     async fn set_option(&mut self, option: String, value: String) -> Result<()> {
-        let cmd = format!("setoption name {} value {}\n", option, value);
-        self.send_command(cmd).await
+        let opt = self.find_option(&option)?;
+        match opt.opt_type {
+            OptionType::Check { .. } => match value.to_lowercase().parse::<bool>() {
+                Ok(value) => self.set_check(&option, value).await,
+                Err(_) => Err(OptionError::InvalidValue {
+                    name: opt.name,
+                    value,
+                    expected: "true or false".to_string(),
+                }
+                .into()),
+            },
+            OptionType::Spin { min, max, .. } => match value.parse::<isize>() {
+                Ok(value) => self.set_spin(&option, value).await,
+                Err(_) => Err(OptionError::InvalidValue {
+                    name: opt.name,
+                    value,
+                    expected: format!("an integer between {} and {}", min, max),
+                }
+                .into()),
+            },
+            OptionType::Combo { .. } => self.set_combo(&option, &value).await,
+            OptionType::String { .. } => self.set_string(&option, &value).await,
+            OptionType::Button => self.press_button(&option).await,
+        }
+    }
+
+    async fn set_check(&mut self, option: &str, value: bool) -> Result<()> {
+        let opt = self.find_option(option)?;
+        match opt.opt_type {
+            OptionType::Check { .. } => self.send_option(&opt.name, Some(value.to_string())).await,
+            opt_type => Err(OptionError::wrong_type(opt.name, opt_type, "check").into()),
+        }
+    }
+
+    async fn set_spin(&mut self, option: &str, value: isize) -> Result<()> {
+        let opt = self.find_option(option)?;
+        match opt.opt_type {
+            OptionType::Spin { min, max, .. } if value < min || value > max => {
+                Err(OptionError::OutOfRange {
+                    name: opt.name,
+                    value,
+                    min,
+                    max,
+                }
+                .into())
+            }
+            OptionType::Spin { .. } => self.send_option(&opt.name, Some(value.to_string())).await,
+            opt_type => Err(OptionError::wrong_type(opt.name, opt_type, "spin").into()),
+        }
+    }
+
+    async fn set_combo(&mut self, option: &str, value: &str) -> Result<()> {
+        let opt = self.find_option(option)?;
+        match opt.opt_type {
+            OptionType::Combo { options, .. } => {
+                match options.iter().find(|o| o.eq_ignore_ascii_case(value)) {
+                    Some(value) => self.send_option(&opt.name, Some(value.clone())).await,
+                    None => Err(OptionError::InvalidValue {
+                        name: opt.name,
+                        value: value.to_string(),
+                        expected: format!("one of: {}", options.join(", ")),
+                    }
+                    .into()),
+                }
+            }
+            opt_type => Err(OptionError::wrong_type(opt.name, opt_type, "combo").into()),
+        }
+    }
+
+    async fn set_string(&mut self, option: &str, value: &str) -> Result<()> {
+        let opt = self.find_option(option)?;
+        match opt.opt_type {
+            OptionType::String { .. } => self.send_option(&opt.name, Some(value.to_string())).await,
+            opt_type => Err(OptionError::wrong_type(opt.name, opt_type, "string").into()),
+        }
+    }
+
+    async fn press_button(&mut self, option: &str) -> Result<()> {
+        let opt = self.find_option(option)?;
+        match opt.opt_type {
+            OptionType::Button => self.send_option(&opt.name, None).await,
+            opt_type => Err(OptionError::wrong_type(opt.name, opt_type, "button").into()),
+        }
     }
 }
 
 /// Engine evaluation info
 #[derive(Debug, Clone, PartialEq)]
 pub struct Evaluation {
-    pub score: isize,
-    pub mate: isize,
+    /// Latest score, `None` until the engine sends one
+    pub score: Option<Score>,
+    /// Whether the score is exact or a bound
+    pub bound: Bound,
+    pub wdl: Option<Wdl>,
     pub depth: isize,
     pub nodes: isize,
     pub seldepth: isize,
     pub multipv: isize,
     pub pv: Vec<String>,
     pub time: isize,
+    pub nps: isize,
+    pub hashfull: isize,
+    pub tbhits: isize,
+    pub sbhits: isize,
+    pub cpuload: isize,
+    pub currmove: Option<String>,
+    pub currmovenumber: isize,
+    pub refutation: Vec<String>,
+    pub currline: Vec<String>,
+    pub string: Option<String>,
+    /// Position searched, when known, to show the pv in SAN
+    pub board: Option<Board>,
 }
 
 impl Default for Evaluation {
     /// Create evaluation with empty values
     fn default() -> Self {
         Evaluation {
-            score: 0,
-            mate: 0,
+            score: None,
+            bound: Bound::Exact,
+            wdl: None,
             depth: 0,
             nodes: 0,
             seldepth: 0,
             multipv: 0,
             pv: vec![],
             time: 0,
+            nps: 0,
+            hashfull: 0,
+            tbhits: 0,
+            sbhits: 0,
+            cpuload: 0,
+            currmove: None,
+            currmovenumber: 0,
+            refutation: vec![],
+            currline: vec![],
+            string: None,
+            board: None,
+        }
+    }
+}
+
+impl Evaluation {
+    /// Win, draw and loss expectation of the side to move, as reported by the engine or
+    /// estimated from the score with the default model and the material left on the board
+    /// searched, when it's known
+    pub fn wdl(&self) -> Option<Wdl> {
+        self.wdl_with(
+            WdlModel::default(),
+            self.board.as_ref().map(Board::material),
+        )
+    }
+
+    /// Win, draw and loss expectation of the side to move, as reported by the engine or
+    /// estimated from the score with the given model and the material left on the board
+    pub fn wdl_with(&self, model: WdlModel, material: Option<u32>) -> Option<Wdl> {
+        match (self.wdl, self.score) {
+            (Some(wdl), _) => Some(wdl),
+            (None, Some(score)) => Some(model.wdl(score, material)),
+            (None, None) => None,
         }
     }
+
+    /// Update the evaluation with the values present in an info line
+    fn update(self, info: Info) -> Self {
+        let score = match (info.mate, info.cp) {
+            (Some(mate), _) => Some(Score::Mate(clamp(mate))),
+            (None, Some(cp)) => Some(Score::Centipawns(clamp(cp))),
+            (None, None) => None,
+        };
+        // bounds and the wdl only apply to the score they were sent with
+        let bound = match (score, info.lowerbound, info.upperbound) {
+            (None, _, _) => self.bound,
+            (Some(_), true, _) => Bound::Lower,
+            (Some(_), _, true) => Bound::Upper,
+            (Some(_), false, false) => Bound::Exact,
+        };
+        let wdl = match score {
+            Some(_) => info.wdl,
+            None => info.wdl.or(self.wdl),
+        };
+        Evaluation {
+            score: score.or(self.score),
+            bound,
+            wdl,
+            depth: info.depth.unwrap_or(self.depth),
+            nodes: info.nodes.unwrap_or(self.nodes),
+            seldepth: info.seldepth.unwrap_or(self.seldepth),
+            multipv: info.multipv.unwrap_or(self.multipv),
+            pv: info.pv.unwrap_or(self.pv),
+            time: info.time.unwrap_or(self.time),
+            nps: info.nps.unwrap_or(self.nps),
+            hashfull: info.hashfull.unwrap_or(self.hashfull),
+            tbhits: info.tbhits.unwrap_or(self.tbhits),
+            sbhits: info.sbhits.unwrap_or(self.sbhits),
+            cpuload: info.cpuload.unwrap_or(self.cpuload),
+            // only valid for the update that sent them
+            currmove: info.currmove,
+            currmovenumber: info.currmovenumber.unwrap_or_default(),
+            refutation: info.refutation.unwrap_or(self.refutation),
+            currline: info.currline.unwrap_or(self.currline),
+            string: info.string,
+            board: self.board,
+        }
+    }
+
+    /// The pv in Standard Algebraic Notation with move numbers, i.e. `8...Qa5 9.Qxa5 Nxa5`.
+    /// `None` if the position searched isn't known or the pv isn't legal in it.
+    pub fn pv_san(&self) -> Option<String> {
+        self.board.as_ref()?.san_line(&self.pv).ok()
+    }
 }
 
 impl Display for Evaluation {
-    /// The alternate ("{:#}") operator will add the moves in pv to the output
+    /// The alternate ("{:#}") operator will add the moves in pv to the output, in SAN when the
+    /// position searched is known
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.score {
+            Some(score) => f.write_fmt(format_args!("score: {}", score))?,
+            None => f.write_str("score: -")?,
+        }
+        match self.bound {
+            Bound::Exact => {}
+            Bound::Lower => f.write_str(" lowerbound")?,
+            Bound::Upper => f.write_str(" upperbound")?,
+        }
         f.write_fmt(format_args!(
-            "score: {} mate: {} depth: {} nodes: {} seldepth: {} multipv: {} time: {}",
-            self.score, self.mate, self.depth, self.nodes, self.seldepth, self.multipv, self.time
+            " depth: {} nodes: {} seldepth: {} multipv: {} time: {} nps: {}",
+            self.depth, self.nodes, self.seldepth, self.multipv, self.time, self.nps
         ))?;
+        if let Some(wdl) = self.wdl {
+            f.write_fmt(format_args!(" wdl: {} {} {}", wdl.win, wdl.draw, wdl.loss))?;
+        }
         if f.alternate() {
-            f.write_fmt(format_args!("\npv: {}", self.pv.join(", ")))?;
+            match self.pv_san() {
+                Some(pv) => f.write_fmt(format_args!("\npv: {}", pv))?,
+                None => f.write_fmt(format_args!("\npv: {}", self.pv.join(", ")))?,
+            }
         }
         Ok(())
     }
 }
 
+/// Fit a score sent by the engine into a `Score`, saturating values out of range
+fn clamp(value: isize) -> i32 {
+    // the lowest value is left out so that every score can be negated
+    value.clamp(-(i32::MAX as isize), i32::MAX as isize) as i32
+}
+
+/// Result of a finished search
+#[derive(Debug, Clone, PartialEq)]
+pub struct BestMove {
+    /// Best move found by the engine
+    pub bestmove: String,
+    /// Move the engine expects as a reply, if any
+    pub ponder: Option<String>,
+    /// Last evaluation received before the search finished
+    pub evaluation: Option<Evaluation>,
+}
+
 /// Posible engine states
 #[derive(PartialEq, Debug)]
 enum EngineStateEnum {
@@ -248,6 +1003,125 @@ enum EngineStateEnum {
     Initialized,
     Ready,
     Thinking,
+    /// The engine is searching the position after the expected reply, waiting for `ponderhit`
+    Pondering,
+    /// The engine was asked to quit
+    Exited,
+    /// The engine exited without being asked to
+    Crashed,
+}
+
+impl EngineStateEnum {
+    fn has_exited(&self) -> bool {
+        matches!(self, EngineStateEnum::Exited | EngineStateEnum::Crashed)
+    }
+
+    fn is_searching(&self) -> bool {
+        matches!(self, EngineStateEnum::Thinking | EngineStateEnum::Pondering)
+    }
+}
+
+/// Errors produced while communicating with an engine
+#[derive(Error, Debug)]
+pub enum EngineError {
+    /// The engine process couldn't be started
+    #[error("couldn't start engine at '{path}'")]
+    Spawn {
+        path: String,
+        source: std::io::Error,
+    },
+
+    /// Reading from or writing to the engine failed
+    #[error("engine i/o failed")]
+    Io(#[from] std::io::Error),
+
+    /// The engine can't handle the request in its current state
+    #[error("unexpected engine state: {0}")]
+    UnexpectedState(String),
+
+    /// An option couldn't be set
+    #[error(transparent)]
+    InvalidOption(#[from] OptionError),
+
+    /// The position to search is malformed or has illegal moves
+    #[error("invalid position")]
+    InvalidPosition(#[from] PositionError),
+
+    /// A move to search isn't legal in the position
+    #[error(transparent)]
+    IllegalMove(#[from] MoveError),
+
+    /// The engine answered with a best move that can't be played in the position searched
+    #[error("engine sent an illegal best move: {0}")]
+    IllegalBestMove(MoveError),
+
+    /// A message from the engine couldn't be parsed
+    #[error(transparent)]
+    Parse(#[from] UCIError),
+
+    /// The engine exited without being asked to
+    #[error(
+        "engine exited unexpectedly with {}{}",
+        display_status(.status),
+        display_stderr(.stderr)
+    )]
+    Crashed {
+        /// Exit status of the process, if it could be reaped
+        status: Option<ExitStatus>,
+        /// Last lines written by the engine to stderr
+        stderr: Vec<String>,
+    },
+
+    /// The engine reported its copy protection check failed, so it must not be used
+    #[error("engine copy protection check failed")]
+    CopyProtection,
+
+    /// The engine rejected the registration name and code
+    #[error("engine registration failed")]
+    Registration,
+
+    /// The engine didn't send the expected response in time
+    #[error("engine didn't respond with {response} within {timeout:?}")]
+    Timeout {
+        /// Response that was expected, i.e. `readyok`
+        response: &'static str,
+        timeout: Duration,
+    },
+}
+
+/// Result of an engine operation
+pub type Result<T> = std::result::Result<T, EngineError>;
+
+fn display_status(status: &Option<ExitStatus>) -> String {
+    match status {
+        Some(status) => status.to_string(),
+        None => "unknown status".to_string(),
+    }
+}
+
+fn display_stderr(stderr: &[String]) -> String {
+    if stderr.is_empty() {
+        String::new()
+    } else {
+        format!(", stderr:\n{}", stderr.join("\n"))
+    }
+}
+
+/// Engine identification, fields are empty until the engine sends them
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct EngineId {
+    pub name: String,
+    pub author: String,
+}
+
+impl Display for EngineId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.name.is_empty(), self.author.is_empty()) {
+            (true, _) => f.write_str("unknown engine"),
+            (false, true) => f.write_str(&self.name),
+            (false, false) => f.write_fmt(format_args!("{} by {}", self.name, self.author)),
+        }
+    }
 }
 
 #[derive(PartialEq, Debug, Clone)]
@@ -256,81 +1130,258 @@ pub struct EngineOption {
     pub opt_type: OptionType,
 }
 
+/// Errors produced when setting engine options
+#[derive(Error, Debug, PartialEq)]
+pub enum OptionError {
+    /// The engine doesn't advertise an option with this name
+    #[error("unknown option '{0}', see the engine options for available ones")]
+    Unknown(String),
+
+    /// The option was set with a value of another type
+    #[error("option '{name}' is of type {actual}, not {expected}")]
+    WrongType {
+        name: String,
+        actual: &'static str,
+        expected: &'static str,
+    },
+
+    /// The value for a spin option is outside its bounds
+    #[error("value {value} for option '{name}' is out of range, expected between {min} and {max}")]
+    OutOfRange {
+        name: String,
+        value: isize,
+        min: isize,
+        max: isize,
+    },
+
+    /// The value can't be used for the option
+    #[error("invalid value '{value}' for option '{name}', expected {expected}")]
+    InvalidValue {
+        name: String,
+        value: String,
+        expected: String,
+    },
+}
+
+impl OptionError {
+    fn wrong_type(name: String, opt_type: OptionType, expected: &'static str) -> Self {
+        let actual = match opt_type {
+            OptionType::Check { .. } => "check",
+            OptionType::Spin { .. } => "spin",
+            OptionType::Combo { .. } => "combo",
+            OptionType::Button => "button",
+            OptionType::String { .. } => "string",
+        };
+        OptionError::WrongType {
+            name,
+            actual,
+            expected,
+        }
+    }
+}
+
 /// Engine state handler with async stdout parsing
+#[derive(Clone)]
 struct EngineState {
-    state: Arc<Mutex<EngineStateEnum>>,
-    evaluation: Arc<Mutex<Option<Evaluation>>>,
+    state: Arc<watch::Sender<EngineStateEnum>>,
+    stderr: Arc<Mutex<VecDeque<String>>>,
+    evaluations: Arc<Mutex<BTreeMap<isize, Evaluation>>>,
+    board: Arc<Mutex<Option<Board>>>,
     options: Arc<Mutex<Vec<EngineOption>>>,
+    id: Arc<Mutex<EngineId>>,
+    best_move: Arc<watch::Sender<Option<BestMove>>>,
+    copyprotection: Arc<watch::Sender<Option<CheckStatus>>>,
+    registration: Arc<watch::Sender<Option<CheckStatus>>>,
+    events: broadcast::Sender<UCI>,
+    raw: broadcast::Sender<String>,
+    stderr_lines: broadcast::Sender<String>,
+    trace: Arc<Mutex<Option<Trace>>>,
 }
 
 impl EngineState {
-    async fn new(stdout: ChildStdout) -> Self {
-        let ev = Arc::new(Mutex::new(None));
-        let state = Arc::new(Mutex::new(EngineStateEnum::Uninitialized));
-        let options = Arc::new(Mutex::new(Vec::new()));
-        let stdout = BufReader::new(stdout);
+    fn new(
+        stdout: EngineReader,
+        stderr: Option<EngineReader>,
+        trace: Option<Trace>,
+    ) -> (Self, JoinHandle<()>) {
+        let (state, _) = watch::channel(EngineStateEnum::Uninitialized);
+        let (best_move, _) = watch::channel(None);
+        let (copyprotection, _) = watch::channel(None);
+        let (registration, _) = watch::channel(None);
+        let (events, _) = broadcast::channel(EVENTS_CAPACITY);
+        let (raw, _) = broadcast::channel(EVENTS_CAPACITY);
+        let (stderr_lines, _) = broadcast::channel(EVENTS_CAPACITY);
         let engstate = EngineState {
-            state: state.clone(),
-            evaluation: ev.clone(),
-            options: options.clone(),
+            state: Arc::new(state),
+            stderr: Arc::new(Mutex::new(VecDeque::with_capacity(STDERR_CAPACITY))),
+            evaluations: Arc::new(Mutex::new(BTreeMap::new())),
+            board: Arc::new(Mutex::new(None)),
+            options: Arc::new(Mutex::new(Vec::new())),
+            id: Arc::new(Mutex::new(EngineId::default())),
+            best_move: Arc::new(best_move),
+            copyprotection: Arc::new(copyprotection),
+            registration: Arc::new(registration),
+            events,
+            raw,
+            stderr_lines,
+            trace: Arc::new(Mutex::new(trace)),
         };
-        tokio::spawn(async move {
-            Self::process_stdout(stdout, state.clone(), ev.clone(), options.clone()).await
+        let stdout = BufReader::new(stdout);
+        let reader = engstate.clone();
+        let handle = tokio::spawn(async move {
+            match stderr {
+                Some(stderr) => {
+                    let stderr = BufReader::new(stderr);
+                    tokio::join!(reader.process_stdout(stdout), reader.process_stderr(stderr));
+                }
+                None => reader.process_stdout(stdout).await,
+            }
         });
-        return engstate;
+        (engstate, handle)
     }
 
-    async fn process_stdout(
-        mut stdout: BufReader<ChildStdout>,
-        state: Arc<Mutex<EngineStateEnum>>,
-        ev: Arc<Mutex<Option<Evaluation>>>,
-        options: Arc<Mutex<Vec<EngineOption>>>,
-    ) {
+    async fn process_stdout(&self, mut stdout: BufReader<EngineReader>) {
+        let mut buf = Vec::new();
         loop {
-            let mut str = String::new();
-            stdout.read_line(&mut str).await.unwrap();
-            match parse_uci(str) {
-                Ok(UCI::UciOk) => {
-                    let mut state = state.lock().expect("couldn't aquire state lock");
-                    *state = EngineStateEnum::Initialized;
-                }
-                Ok(UCI::ReadyOk) => {
-                    let mut state = state.lock().expect("couldn't aquire state lock");
-                    *state = EngineStateEnum::Ready;
-                }
-                Ok(UCI::Info {
-                    cp,
-                    mate,
-                    depth,
-                    nodes,
-                    seldepth,
-                    time,
-                    multipv,
-                    pv,
-                }) => {
-                    let mut ev = ev.lock().expect("couldn't aquire ev lock");
-                    let def_ev = Evaluation::default();
-                    let prev_ev = match ev.as_ref() {
-                        Some(ev) => ev,
-                        None => &def_ev,
-                    };
-                    *ev = Some(Evaluation {
-                        score: cp.unwrap_or(prev_ev.score),
-                        mate: mate.unwrap_or(prev_ev.mate),
-                        depth: depth.unwrap_or(prev_ev.depth),
-                        nodes: nodes.unwrap_or(prev_ev.nodes),
-                        seldepth: seldepth.unwrap_or(prev_ev.seldepth),
-                        multipv: multipv.unwrap_or(prev_ev.multipv),
-                        pv: pv.unwrap_or(prev_ev.pv.clone()),
-                        time: time.unwrap_or(prev_ev.time),
-                    });
+            buf.clear();
+            match stdout.read_until(b'\n', &mut buf).await {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {}
+            }
+            // engines may send names in other encodings, which mustn't be taken for a crash
+            let str = String::from_utf8_lossy(&buf);
+            self.record(Direction::Received, str.trim_end());
+            // sending only fails when there are no subscribers
+            let _ = self.raw.send(str.trim_end().to_string());
+            let line = str.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let msg = match parse_uci(line.to_string()) {
+                Ok(msg) => msg,
+                Err(_) => UCI::Unknown(line.to_string()),
+            };
+            self.process_message(msg.clone());
+            let _ = self.events.send(msg);
+        }
+        // stdout is closed once the engine exits, which is a crash unless it was asked to quit
+        self.state.send_if_modified(|state| match state {
+            EngineStateEnum::Exited | EngineStateEnum::Crashed => false,
+            _ => {
+                *state = EngineStateEnum::Crashed;
+                true
+            }
+        });
+    }
+
+    /// Keep the last lines written to stderr to report them if the engine crashes, and stream
+    /// them to subscribers
+    async fn process_stderr(&self, mut stderr: BufReader<EngineReader>) {
+        let mut buf = Vec::new();
+        loop {
+            buf.clear();
+            match stderr.read_until(b'\n', &mut buf).await {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {}
+            }
+            let line = String::from_utf8_lossy(&buf).trim_end().to_string();
+            let mut lines = self.stderr.lock().expect("couldn't aquire stderr lock");
+            if lines.len() == STDERR_CAPACITY {
+                lines.pop_front();
+            }
+            lines.push_back(line.clone());
+            let _ = self.stderr_lines.send(line);
+        }
+    }
+
+    /// Change current engine state, an exited engine stays exited
+    fn set_state(&self, new_state: EngineStateEnum) {
+        self.state.send_if_modified(|state| {
+            if state.has_exited() {
+                return false;
+            }
+            *state = new_state;
+            true
+        });
+    }
+
+    /// Record a line in the protocol trace, if there is one
+    fn record(&self, direction: Direction, line: &str) {
+        if let Some(trace) = self
+            .trace
+            .lock()
+            .expect("couldn't aquire trace lock")
+            .as_ref()
+        {
+            trace.record(direction, line);
+        }
+    }
+
+    /// Update the engine state from a message sent by the engine
+    fn process_message(&self, msg: UCI) {
+        match msg {
+            UCI::Id { name, author } => {
+                let mut id = self.id.lock().expect("couldn't aquire id lock");
+                if let Some(name) = name {
+                    id.name = name;
                 }
-                Ok(UCI::Option { name, opt_type }) => {
-                    let mut options = options.lock().expect("couldn't aquire options lock");
-                    options.push(EngineOption { name, opt_type });
+                if let Some(author) = author {
+                    id.author = author;
                 }
-                _ => continue,
             }
+            UCI::UciOk => {
+                self.set_state(EngineStateEnum::Initialized);
+            }
+            UCI::ReadyOk => {
+                self.set_state(EngineStateEnum::Ready);
+            }
+            // `info string` lines aren't about any line being searched, they're only broadcast
+            UCI::Info(info)
+                if (Info {
+                    string: None,
+                    ..info.clone()
+                }) == Info::default() => {}
+            UCI::Info(mut info) => {
+                let mut evs = self.evaluations.lock().expect("couldn't aquire ev lock");
+                // lines without a multipv index refer to the best line
+                let multipv = *info.multipv.get_or_insert(1);
+                let prev_ev = evs.remove(&multipv).unwrap_or_default();
+                let board = self
+                    .board
+                    .lock()
+                    .expect("couldn't aquire board lock")
+                    .clone();
+                evs.insert(multipv, Evaluation { board, ..prev_ev }.update(info));
+            }
+            UCI::Option { name, opt_type } => {
+                let mut options = self.options.lock().expect("couldn't aquire options lock");
+                // engines list their options again every time they receive `uci`
+                options.retain(|opt| opt.name != name);
+                options.push(EngineOption { name, opt_type });
+            }
+            UCI::BestMove { bestmove, ponder } => {
+                let evaluation = self
+                    .evaluations
+                    .lock()
+                    .expect("couldn't aquire ev lock")
+                    .values()
+                    .next()
+                    .cloned();
+                self.best_move.send_replace(Some(BestMove {
+                    bestmove,
+                    ponder,
+                    evaluation,
+                }));
+                // published after the move, a search seen as finished always has its result
+                self.set_state(EngineStateEnum::Initialized);
+            }
+            UCI::CopyProtection(status) => {
+                self.copyprotection.send_replace(Some(status));
+            }
+            UCI::Registration(status) => {
+                self.registration.send_replace(Some(status));
+            }
+            UCI::Unknown(_) => {}
         }
     }
 }
@@ -339,18 +1390,530 @@ impl EngineState {
 mod test {
     use anyhow::Result;
 
-    use crate::engine::{ChessEngine, Engine};
+    use crate::board::FenError;
+    use crate::builder::EngineBuilder;
+    use crate::engine::{ChessEngine, Engine, EngineError, Evaluation, OptionError};
+    use crate::go::GoParams;
+    use crate::moves::MoveError;
+    use crate::parse::{parse_uci, CheckStatus, Wdl, UCI};
+    use crate::position::{Position, PositionError};
+    use crate::score::{Bound, Score};
+    use crate::trace::Trace;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, DuplexStream};
+    use tokio::time::Duration;
+    use tokio_stream::StreamExt;
+
+    #[tokio::test]
+    async fn test_sf() -> Result<()> {
+        let mut sf = Engine::new(test_file!("fakefish.sh")).await?;
+        sf.start_uci().await?;
+        assert_eq!(sf.info().name, "Stockfish 15");
+        assert_eq!(
+            sf.info().author,
+            "the Stockfish developers (see AUTHORS file)"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_best_move() -> Result<()> {
+        let mut sf = Engine::new(test_file!("fakefish.sh")).await?;
+        sf.start_uci().await?;
+        assert!(sf.best_move().await.is_err());
+        sf.go_depth(2).await?;
+        let bm = sf.best_move().await?;
+        assert_eq!(bm.bestmove, "a8b8");
+        assert_eq!(bm.ponder, Some("f4d6".to_string()));
+        assert_eq!(
+            bm.evaluation.and_then(|ev| ev.score),
+            Some(Score::Centipawns(-27))
+        );
+        Ok(())
+    }
 
-    macro_rules! test_file {
-        ($fname:expr) => {
-            concat!(env!("CARGO_MANIFEST_DIR"), "/res/test/", $fname)
+    #[tokio::test]
+    async fn test_multipv_lines() -> Result<()> {
+        let mut sf = Engine::new(test_file!("fakefish.sh")).await?;
+        sf.start_uci().await?;
+        sf.set_spin("MultiPV", 2).await?;
+        let fen = "r2qk2r/pp3ppp/B1nbpn2/2pp1b2/Q2P1B2/2P1PN2/PP1N1PPP/R3K2R b KQkq - 4 8";
+        sf.set_position(&Position::fen(fen)).await?;
+        sf.go_depth(2).await?;
+        sf.best_move().await?;
+        let lines = sf.get_lines().await;
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].pv_san(), Some("8...Rb8 9.Bxd6".to_string()));
+        assert!(format!("{:#}", lines[1]).ends_with("\npv: 8...Bxf4 9.exf4"));
+        let unknown = Evaluation {
+            board: None,
+            ..lines[1].clone()
         };
+        assert!(format!("{:#}", unknown).ends_with("\npv: d6f4, e3f4"));
+        assert_eq!(
+            (lines[0].multipv, lines[0].score),
+            (1, Some(Score::Centipawns(-27)))
+        );
+        assert_eq!(
+            (lines[1].multipv, lines[1].score),
+            (2, Some(Score::Centipawns(-45)))
+        );
+        assert_eq!(sf.get_evaluation().await, Some(lines[0].clone()));
+        Ok(())
     }
 
     #[tokio::test]
-    async fn test_sf() -> Result<()> {
+    async fn test_ponder() -> Result<()> {
+        let mut sf = Engine::new(test_file!("fakefish.sh")).await?;
+        sf.start_uci().await?;
+        let fen = "r2qk2r/pp3ppp/B1nbpn2/2pp1b2/Q2P1B2/2P1PN2/PP1N1PPP/R3K2R b KQkq - 4 8";
+        let position = Position::fen(fen).moves(["a8b8"]);
+        assert!(sf.ponderhit().await.is_err());
+        sf.ponder(&position, "b2b3", GoParams::new().movetime(1000))
+            .await?;
+        assert!(sf.best_move().await.is_err());
+        sf.ponderhit().await?;
+        let bm = sf.best_move().await?;
+        assert_eq!(bm.bestmove, "d6f4");
+
+        sf.ponder(&position, "b2b3", GoParams::new().movetime(1000))
+            .await?;
+        sf.ponder_miss().await?;
+        assert!(sf.best_move().await.is_err());
+        assert_eq!(sf.get_lines().await, vec![]);
+        sf.set_position(&Position::fen(fen)).await?;
+        sf.go_depth(2).await?;
+        assert_eq!(sf.best_move().await?.bestmove, "a8b8");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_trace() -> Result<()> {
+        let path = std::env::temp_dir().join(format!("async-uci-trace-{}", std::process::id()));
+        let mut sf = Engine::builder(test_file!("fakefish.sh"))
+            .trace(Trace::to_file(&path)?)
+            .spawn()
+            .await?;
+        sf.start_uci().await?;
+        sf.set_debug(true).await?;
+        sf.shutdown().await?;
+        let trace = std::fs::read_to_string(&path)?;
+        std::fs::remove_file(&path)?;
+        let lines: Vec<&str> = trace.lines().map(|l| l.trim_start()).collect();
+        let traced = [
+            "< fakefish test",
+            "> uci",
+            "< uciok",
+            "> isready",
+            "< readyok",
+            "> debug on",
+            "> quit",
+        ];
+        for line in traced {
+            assert!(
+                lines.iter().any(|l| l.ends_with(line)),
+                "{} not traced",
+                line
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_evaluation_update() -> Result<()> {
+        let info = |line: &str| match parse_uci(line.to_string()) {
+            Ok(UCI::Info(info)) => info,
+            msg => panic!("not an info line: {:?}", msg),
+        };
+        let ev = Evaluation::default().update(info("info depth 10 score cp 35 lowerbound"));
+        assert_eq!(ev.score, Some(Score::Centipawns(35)));
+        assert_eq!(ev.bound, Bound::Lower);
+        let ev = ev.update(info("info depth 11 nodes 1000"));
+        assert_eq!(ev.bound, Bound::Lower);
+        let ev = ev.update(info("info depth 12 score mate -3"));
+        assert_eq!((ev.score, ev.bound), (Some(Score::Mate(-3)), Bound::Exact));
+        let ev = ev.update(info("info depth 13 score cp -900 upperbound"));
+        assert_eq!(
+            (ev.score, ev.bound),
+            (Some(Score::Centipawns(-900)), Bound::Upper)
+        );
+        assert!(ev
+            .to_string()
+            .starts_with("score: -9.00 upperbound depth: 13"));
+        assert!(ev.wdl().map(|wdl| wdl.loss > 900).unwrap_or(false));
+        let ev = ev.update(info("info depth 14 score cp 20 wdl 60 900 40"));
+        let wdl = Wdl {
+            win: 60,
+            draw: 900,
+            loss: 40,
+        };
+        assert_eq!(ev.wdl(), Some(wdl));
+        assert_eq!(ev.clone().update(info("info nodes 2000")).wdl(), Some(wdl));
+        // a new score without a wdl falls back to the model
+        let rescored = ev.clone().update(info("info depth 15 score cp 20"));
+        assert_eq!(rescored.wdl, None);
+        assert_ne!(rescored.wdl(), Some(wdl));
+        assert_eq!(Evaluation::default().wdl(), None);
+        let endgame = Evaluation {
+            score: Some(Score::Centipawns(200)),
+            board: Some("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1".parse()?),
+            ..Default::default()
+        };
+        let unknown = Evaluation {
+            board: None,
+            ..endgame.clone()
+        };
+        assert!(endgame.wdl().unwrap().win < unknown.wdl().unwrap().win);
+
+        let ev = ev.update(info(
+            "info depth 14 currmove e2e4 currmovenumber 3 string hi",
+        ));
+        assert_eq!(ev.currmove.as_deref(), Some("e2e4"));
+        assert_eq!((ev.currmovenumber, ev.string.as_deref()), (3, Some("hi")));
+        let ev = ev.update(info("info depth 15 score cp 25"));
+        assert_eq!((ev.currmove, ev.currmovenumber, ev.string), (None, 0, None));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_invalid_position() -> Result<()> {
+        let mut sf = Engine::new(test_file!("fakefish.sh")).await?;
+        sf.start_uci().await?;
+        let position = Position::fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0");
+        assert!(matches!(
+            sf.set_position(&position).await,
+            Err(EngineError::InvalidPosition(PositionError::Fen(
+                FenError::FieldCount(5)
+            )))
+        ));
+        assert!(matches!(
+            sf.set_position(&Position::startpos().moves(["e2e4", "e2e4"]))
+                .await,
+            Err(EngineError::InvalidPosition(PositionError::Move(
+                MoveError::Illegal { .. }
+            )))
+        ));
+        sf.set_position(&Position::startpos()).await?;
+        assert!(matches!(
+            sf.go(GoParams::new().searchmoves(["e2e5"])).await,
+            Err(EngineError::IllegalMove(_))
+        ));
+        // fakefish always answers a8b8, which white can't play
+        sf.go_depth(1).await?;
+        assert!(matches!(
+            sf.best_move().await,
+            Err(EngineError::IllegalBestMove(_))
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_chess960() -> Result<()> {
+        let mut sf = Engine::new(test_file!("fakefish.sh")).await?;
+        sf.start_uci().await?;
+        // Shredder-FEN castling rights and castling as the king taking its rook
+        let position =
+            Position::fen("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9")
+                .moves(["g1h1"]);
+        assert!(sf.set_position(&position).await.is_err());
+        sf.set_check("UCI_Chess960", true).await?;
+        sf.set_position(&position).await?;
+        sf.go_depth(1).await?;
+        assert_eq!(sf.best_move().await?.bestmove, "a8b8");
+        sf.set_option("UCI_Chess960".to_string(), "false".to_string())
+            .await?;
+        assert!(sf.set_position(&position).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_subscribe() -> Result<()> {
+        let mut sf = Engine::new(test_file!("fakefish.sh")).await?;
+        sf.start_uci().await?;
+        let mut events = sf.subscribe();
+        sf.go_depth(2).await?;
+        let mut infos = 0;
+        while let Some(msg) = events.next().await {
+            match msg? {
+                UCI::Info(_) => infos += 1,
+                UCI::BestMove { bestmove, .. } => {
+                    assert_eq!(bestmove, "a8b8");
+                    break;
+                }
+                msg => panic!("unexpected message: {:?}", msg),
+            }
+        }
+        // the info string is broadcast too
+        assert_eq!(infos, 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_raw() -> Result<()> {
+        let mut sf = Engine::new(test_file!("fakefish.sh")).await?;
+        let mut raw = sf.subscribe_raw();
+        sf.send_raw("uci").await?;
+        let mut lines = Vec::new();
+        while let Some(line) = raw.next().await {
+            let line = line?;
+            if line == "uciok" {
+                break;
+            }
+            lines.push(line);
+        }
+        assert!(lines.contains(&"id name Stockfish 15".to_string()));
+        assert!(lines.contains(&"".to_string()));
+        assert!(lines.contains(&"option name Clear Hash type button".to_string()));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_set_option() -> Result<()> {
         let mut sf = Engine::new(test_file!("fakefish.sh")).await?;
         sf.start_uci().await?;
+        sf.set_spin("hash", 128).await?;
+        sf.set_check("Ponder", true).await?;
+        sf.set_string("SyzygyPath", "/tmp/syzygy").await?;
+        sf.press_button("Clear Hash").await?;
+        sf.set_option("Use NNUE".to_string(), "False".to_string())
+            .await?;
+
+        let err = sf.set_spin("Hash", 4096).await.unwrap_err();
+        match err {
+            EngineError::InvalidOption(err) => assert_eq!(
+                err,
+                OptionError::OutOfRange {
+                    name: "Hash".to_string(),
+                    value: 4096,
+                    min: 1,
+                    max: 2048,
+                }
+            ),
+            err => panic!("unexpected error: {:?}", err),
+        }
+        let err = sf.set_check("Threads", true).await.unwrap_err();
+        assert!(matches!(
+            err,
+            EngineError::InvalidOption(OptionError::WrongType { .. })
+        ));
+        let err = sf
+            .set_option("Threads".to_string(), "many".to_string())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            EngineError::InvalidOption(OptionError::InvalidValue { .. })
+        ));
+        let err = sf.press_button("Self Destruct").await.unwrap_err();
+        assert!(matches!(
+            err,
+            EngineError::InvalidOption(OptionError::Unknown(name)) if name == "Self Destruct"
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_shutdown() -> Result<()> {
+        let mut sf = Engine::new(test_file!("fakefish.sh")).await?;
+        sf.start_uci().await?;
+        let status = sf.shutdown().await?;
+        assert!(status.unwrap().success());
+        assert!(matches!(
+            sf.go_depth(1).await,
+            Err(EngineError::UnexpectedState(_))
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_crash() -> Result<()> {
+        let marker = std::env::temp_dir().join(format!("crashfish-{}-crash", std::process::id()));
+        let _ = std::fs::remove_file(&marker);
+        let mut sf = EngineBuilder::new(test_file!("crashfish.sh"))
+            .env("CRASHFISH_MARKER", &marker)
+            .spawn()
+            .await?;
+        sf.start_uci().await?;
+        sf.go_depth(1).await?;
+        let err = sf.best_move().await.unwrap_err();
+        match err {
+            EngineError::Crashed { status, stderr } => {
+                assert_eq!(status.and_then(|s| s.code()), Some(139));
+                assert_eq!(stderr, vec!["Segmentation fault".to_string()]);
+            }
+            _ => panic!("unexpected error: {:?}", err),
+        }
+        assert!(sf.go_depth(1).await.is_err());
+        std::fs::remove_file(&marker)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_timeout() -> Result<()> {
+        // cat echoes commands back, so it never answers with uciok
+        let mut eng = Engine::new("cat").await?;
+        eng.set_timeout(Duration::from_millis(100));
+        let err = eng.start_uci().await.unwrap_err();
+        assert!(matches!(
+            err,
+            EngineError::Timeout {
+                response: "uciok",
+                ..
+            }
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_spawn_error() {
+        let err = Engine::new(test_file!("nofish.sh")).await.err().unwrap();
+        assert!(matches!(err, EngineError::Spawn { path, .. } if path.ends_with("nofish.sh")));
+    }
+
+    /// Minimal engine answering the handshake and searches through an in-memory pipe
+    async fn memfish(stream: DuplexStream) {
+        let (reader, mut writer) = tokio::io::split(stream);
+        let mut lines = BufReader::new(reader).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            let resp = match line.as_str() {
+                "uci" => "id name Memfish\nuciok\n",
+                "isready" => "info string NNUE evaluation enabled\nreadyok\n",
+                "go depth 1" => "info depth 1 currmove e2e4 currmovenumber 1\ninfo depth 1 score cp 10 pv e2e4\ninfo string done\nbestmove e2e4\n",
+                "go infinite" => "info depth 1 score cp 999 pv d2d4\n",
+                "stop" => {
+                    // answer late, as engines finishing the current iteration do
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                    "info depth 2 score cp 999 pv d2d4\nbestmove d2d4\n"
+                }
+                "quit" => return,
+                _ => continue,
+            };
+            if writer.write_all(resp.as_bytes()).await.is_err() {
+                return;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_from_stream() -> Result<()> {
+        let (client, server) = tokio::io::duplex(1024);
+        let fake = tokio::spawn(memfish(server));
+        let mut eng = Engine::from_stream(client);
+        eng.start_uci().await?;
+        assert_eq!(eng.info().name, "Memfish");
+        // `info string` lines don't create an evaluation
+        assert_eq!(eng.get_evaluation().await, None);
+        eng.go_depth(1).await?;
+        let bm = eng.best_move().await?;
+        assert_eq!(bm.bestmove, "e2e4");
+        let ev = bm.evaluation.expect("no evaluation");
+        assert_eq!(ev.score, Some(Score::Centipawns(10)));
+        assert_eq!((ev.currmove, ev.string), (None, None));
+        assert_eq!(eng.shutdown().await?, None);
+        fake.await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stop() -> Result<()> {
+        let (client, server) = tokio::io::duplex(1024);
+        tokio::spawn(memfish(server));
+        let mut eng = Engine::from_stream(client);
+        eng.start_uci().await?;
+        eng.go_infinite().await?;
+        eng.stop().await?;
+        assert_eq!(eng.best_move().await?.bestmove, "d2d4");
+        eng.go_infinite().await?;
+        eng.stop().await?;
+        eng.go_depth(1).await?;
+        let bm = eng.best_move().await?;
+        assert_eq!(bm.bestmove, "e2e4");
+        assert_eq!(
+            bm.evaluation.and_then(|ev| ev.score),
+            Some(Score::Centipawns(10))
+        );
+        Ok(())
+    }
+
+    /// Fake engine over an in-memory pipe requiring registration, with its copy protection
+    /// failing when `protected` is false
+    async fn lockedfish(stream: DuplexStream, protected: bool) {
+        let (reader, mut writer) = tokio::io::split(stream);
+        let mut lines = BufReader::new(reader).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            let resp = match (line.as_str(), protected) {
+                ("uci", true) => "uciok\ncopyprotection checking\ncopyprotection ok\nregistration checking\nregistration error\n",
+                ("uci", false) => "uciok\ncopyprotection checking\ncopyprotection error\n",
+                ("isready", _) => "readyok\n",
+                ("register name Ann Other code 1234", _) => "registration checking\nregistration ok\n",
+                ("register name Ann Other code 4321", _) => "registration checking\nregistration error\n",
+                ("quit", _) => return,
+                _ => continue,
+            };
+            if writer.write_all(resp.as_bytes()).await.is_err() {
+                return;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_registration() -> Result<()> {
+        let (client, server) = tokio::io::duplex(1024);
+        tokio::spawn(lockedfish(server, true));
+        let mut eng = Engine::from_stream(client);
+        eng.start_uci().await?;
+        assert_eq!(eng.registration(), Some(CheckStatus::Error));
+        assert!(matches!(
+            eng.register("Ann Other", "4321").await,
+            Err(EngineError::Registration)
+        ));
+        eng.register("Ann Other", "1234").await?;
+        assert_eq!(eng.registration(), Some(CheckStatus::Ok));
+        eng.register_later().await?;
+
+        let (client, server) = tokio::io::duplex(1024);
+        tokio::spawn(lockedfish(server, false));
+        let mut eng = Engine::from_stream(client);
+        assert!(matches!(
+            eng.start_uci().await,
+            Err(EngineError::CopyProtection)
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_non_utf8() -> Result<()> {
+        let (client, server) = tokio::io::duplex(1024);
+        tokio::spawn(async move {
+            let (reader, mut writer) = tokio::io::split(server);
+            let mut lines = BufReader::new(reader).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let resp: &[u8] = match line.as_str() {
+                    // a latin-1 author name
+                    "uci" => b"id name Latinfish\nid author J\xf6rg\nuciok\n",
+                    "isready" => b"readyok\n",
+                    _ => continue,
+                };
+                if writer.write_all(resp).await.is_err() {
+                    return;
+                }
+            }
+        });
+        let mut eng = Engine::from_stream(client);
+        eng.start_uci().await?;
+        assert_eq!(eng.info().author, "J\u{fffd}rg");
+        // the engine is still running after the line it couldn't decode
+        eng.new_game().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_from_io_closed() -> Result<()> {
+        let (client, server) = tokio::io::duplex(1024);
+        let (reader, writer) = tokio::io::split(client);
+        let mut eng = Engine::from_io(reader, writer);
+        drop(server);
+        let err = eng.start_uci().await.unwrap_err();
+        assert!(matches!(err, EngineError::Crashed { status: None, .. }));
         Ok(())
     }
 }
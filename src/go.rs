@@ -0,0 +1,189 @@
+use std::fmt::Display;
+
+/// Parameters for the `go` command. Any subset of them can be combined into a single search.
+///
+/// ```
+/// use async_uci::go::GoParams;
+///
+/// let params = GoParams::new().wtime(60000).btime(60000).winc(1000).binc(1000);
+/// assert_eq!(params.to_string(), "go wtime 60000 btime 60000 winc 1000 binc 1000");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GoParams {
+    searchmoves: Vec<String>,
+    ponder: bool,
+    wtime: Option<usize>,
+    btime: Option<usize>,
+    winc: Option<usize>,
+    binc: Option<usize>,
+    movestogo: Option<usize>,
+    depth: Option<usize>,
+    nodes: Option<usize>,
+    mate: Option<usize>,
+    movetime: Option<usize>,
+    infinite: bool,
+}
+
+impl GoParams {
+    /// Create an empty set of parameters, which sends a plain `go`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Restrict the search to the given moves
+    pub fn searchmoves<I, S>(mut self, moves: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.searchmoves = moves.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Start searching in pondering mode
+    pub fn ponder(mut self) -> Self {
+        self.ponder = true;
+        self
+    }
+
+    /// Milliseconds white has left on the clock
+    pub fn wtime(mut self, ms: usize) -> Self {
+        self.wtime = Some(ms);
+        self
+    }
+
+    /// Milliseconds black has left on the clock
+    pub fn btime(mut self, ms: usize) -> Self {
+        self.btime = Some(ms);
+        self
+    }
+
+    /// White increment per move in milliseconds
+    pub fn winc(mut self, ms: usize) -> Self {
+        self.winc = Some(ms);
+        self
+    }
+
+    /// Black increment per move in milliseconds
+    pub fn binc(mut self, ms: usize) -> Self {
+        self.binc = Some(ms);
+        self
+    }
+
+    /// Moves left until the next time control
+    pub fn movestogo(mut self, moves: usize) -> Self {
+        self.movestogo = Some(moves);
+        self
+    }
+
+    /// Search up to a certain depth in plies
+    pub fn depth(mut self, plies: usize) -> Self {
+        self.depth = Some(plies);
+        self
+    }
+
+    /// Search up to a certain amount of nodes
+    pub fn nodes(mut self, nodes: usize) -> Self {
+        self.nodes = Some(nodes);
+        self
+    }
+
+    /// Search for a mate in a certain number of moves
+    pub fn mate(mut self, moves: usize) -> Self {
+        self.mate = Some(moves);
+        self
+    }
+
+    /// Search for exactly the given milliseconds
+    pub fn movetime(mut self, ms: usize) -> Self {
+        self.movetime = Some(ms);
+        self
+    }
+
+    /// Search until explicitly stopped
+    pub fn infinite(mut self) -> Self {
+        self.infinite = true;
+        self
+    }
+
+    /// Check if the search starts in pondering mode
+    pub(crate) fn is_ponder(&self) -> bool {
+        self.ponder
+    }
+
+    /// Moves the search is restricted to, empty to search every move
+    pub(crate) fn searched_moves(&self) -> &[String] {
+        &self.searchmoves
+    }
+
+    /// Drop every limit and clock time, searching the same moves until explicitly stopped
+    pub(crate) fn without_limits(self) -> Self {
+        GoParams {
+            searchmoves: self.searchmoves,
+            infinite: true,
+            ..Self::default()
+        }
+    }
+}
+
+impl Display for GoParams {
+    /// Format as a `go` command, without the trailing newline
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("go")?;
+        if self.ponder {
+            f.write_str(" ponder")?;
+        }
+        let values = [
+            ("wtime", self.wtime),
+            ("btime", self.btime),
+            ("winc", self.winc),
+            ("binc", self.binc),
+            ("movestogo", self.movestogo),
+            ("depth", self.depth),
+            ("nodes", self.nodes),
+            ("mate", self.mate),
+            ("movetime", self.movetime),
+        ];
+        for (name, value) in values {
+            if let Some(value) = value {
+                f.write_fmt(format_args!(" {} {}", name, value))?;
+            }
+        }
+        if self.infinite {
+            f.write_str(" infinite")?;
+        }
+        // searchmoves goes last as the move list has no terminator
+        if !self.searchmoves.is_empty() {
+            f.write_fmt(format_args!(" searchmoves {}", self.searchmoves.join(" ")))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::go::GoParams;
+
+    #[test]
+    fn test_go_params() {
+        assert_eq!(GoParams::new().to_string(), "go");
+        assert_eq!(GoParams::new().infinite().to_string(), "go infinite");
+        assert_eq!(
+            GoParams::new()
+                .searchmoves(["e2e4", "d2d4"])
+                .ponder()
+                .wtime(300000)
+                .btime(290000)
+                .winc(2000)
+                .binc(2000)
+                .movestogo(40)
+                .nodes(1000000)
+                .to_string(),
+            "go ponder wtime 300000 btime 290000 winc 2000 binc 2000 movestogo 40 nodes 1000000 searchmoves e2e4 d2d4"
+        );
+        assert_eq!(
+            GoParams::new().depth(20).movetime(5000).mate(3).to_string(),
+            "go depth 20 mate 3 movetime 5000"
+        );
+    }
+}
@@ -1,2 +1,17 @@
+#[cfg(test)]
+#[macro_use]
+mod testing;
+
+pub mod analysis;
+pub mod board;
+pub mod builder;
 pub mod engine;
+pub mod go;
+pub mod moves;
 pub mod parse;
+pub mod position;
+pub mod san;
+pub mod score;
+pub mod supervisor;
+pub mod trace;
+pub mod wdl;
@@ -1,12 +1,20 @@
 use anyhow::{bail, Result};
+use async_uci::builder::EngineBuilder;
+use async_uci::engine::{ChessEngine, Engine, Evaluation};
+use async_uci::go::GoParams;
+use async_uci::parse::UCI;
+use async_uci::position::Position;
+use async_uci::trace::Trace;
 use clap::Parser;
-use cli::{CLIArgs, Subcommands};
-use engine::{ChessEngine, Engine, Evaluation};
-use tokio::task::yield_now;
+use cli::{CLIArgs, Notation, SearchArgs, Subcommands};
+use tokio_stream::{wrappers::BroadcastStream, StreamExt};
+
+#[cfg(test)]
+#[macro_use]
+mod testing;
 
 mod cli;
-mod engine;
-mod parse;
+mod serve;
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -18,93 +26,219 @@ async fn main() -> Result<()> {
             Err(_) => bail!("Couldn't find engine location. set CHESS_ENGINE_PATH environment variable or pass in --engine-path/-P"),
         },
     };
-    println!("Using engine: {engpath}");
+    let mut builder = EngineBuilder::new(&engpath).args(args.global.engine_args);
+    if let Some(cwd) = args.global.engine_cwd {
+        builder = builder.current_dir(cwd);
+    }
+    if let Some(path) = args.global.trace {
+        builder = builder.trace(Trace::to_file(path)?);
+    }
     match args.command {
-        Subcommands::Search {
-            fen,
-            show_moves,
-            lines,
-            max_depth,
-            max_time,
-            mate_in,
-            options,
-        } => {
-            search(
-                engpath, fen, lines, show_moves, max_depth, max_time, mate_in, options,
-            )
-            .await?
-        }
-        Subcommands::ListOptions {} => list_options(engpath).await?,
+        Subcommands::Search(search_args) => search(builder, engpath, search_args).await?,
+        Subcommands::ListOptions {} => list_options(builder, engpath).await?,
+        Subcommands::Serve(serve_args) => serve::serve(builder, serve_args).await?,
     };
     Ok(())
 }
 
-async fn list_options(engpath: String) -> Result<()> {
-    let mut eng = Engine::new(&engpath).await?;
+async fn list_options(builder: EngineBuilder, engpath: String) -> Result<()> {
+    let mut eng = builder.spawn().await?;
     eng.start_uci().await?;
+    println!("Using engine: {} ({engpath})", eng.info());
     let options = eng.get_options().await?;
     for opt in options {
         println!("{:?}", opt);
     }
+    eng.shutdown().await?;
     Ok(())
 }
 
-async fn search(
-    engpath: String,
-    fen: String,
-    lines: usize,
-    show_moves: bool,
-    max_depth: usize,
-    max_time: usize,
-    mate_in: usize,
-    options: Vec<String>,
-) -> Result<()> {
-    let mut sf = spawn_engine(engpath, fen, lines.to_string(), options).await?;
-    if max_depth > 0 {
-        sf.go_depth(max_depth).await?;
-    } else if max_time > 0 {
-        sf.go_time(max_time).await?;
-    } else if mate_in > 0 {
-        sf.go_mate(mate_in).await?;
+async fn search(builder: EngineBuilder, engpath: String, args: Box<SearchArgs>) -> Result<()> {
+    let (params, limited) = go_params(&args);
+    let moves = args.show_moves.then_some(args.notation);
+    let position = match &args.fen {
+        Some(fen) => Position::fen(fen),
+        None => Position::startpos(),
+    }
+    .moves(args.moves.clone());
+    // keep Ctrl-C from reaching the engine, so that searches can be stopped and the engine quit
+    #[cfg(unix)]
+    let builder = builder.own_process_group(true);
+    let mut sf = spawn_engine(builder, engpath, args.lines, args.options).await?;
+    // a ponder search only ends after ponderhit, which the analysis session can't send
+    if limited || args.ponder {
+        sf.set_position(&position).await?;
+        let events = sf.subscribe();
+        sf.go(params).await?;
+        stream_engine_eval(&mut sf, events, moves, args.ponder).await?;
     } else {
-        sf.go_infinite().await?;
+        analyze(&mut sf, &position, params, moves).await?;
     }
-    stream_engine_eval(&mut sf, show_moves).await?;
+    sf.shutdown().await?;
     Ok(())
 }
 
+/// Build the `go` parameters from the search arguments, and whether any limit was set
+fn go_params(args: &SearchArgs) -> (GoParams, bool) {
+    let mut params = GoParams::new().searchmoves(args.searchmoves.clone());
+    let mut limited = false;
+    if args.max_depth > 0 {
+        params = params.depth(args.max_depth);
+        limited = true;
+    }
+    if args.max_time > 0 {
+        params = params.movetime(args.max_time);
+        limited = true;
+    }
+    if args.mate_in > 0 {
+        params = params.mate(args.mate_in);
+        limited = true;
+    }
+    let optional = [
+        (
+            args.nodes,
+            GoParams::nodes as fn(GoParams, usize) -> GoParams,
+        ),
+        (args.wtime, GoParams::wtime),
+        (args.btime, GoParams::btime),
+        (args.winc, GoParams::winc),
+        (args.binc, GoParams::binc),
+        (args.movestogo, GoParams::movestogo),
+    ];
+    for (value, setter) in optional {
+        if let Some(value) = value {
+            params = setter(params, value);
+            limited = true;
+        }
+    }
+    if args.ponder {
+        params = params.ponder();
+    }
+    (params, limited)
+}
+
 async fn spawn_engine(
+    builder: EngineBuilder,
     path: String,
-    fen: String,
-    lines: String,
-    options: Vec<String>,
+    lines: usize,
+    options: Vec<(String, String)>,
 ) -> Result<Engine> {
-    let mut eng = Engine::new(&path).await?;
+    let mut eng = builder.spawn().await?;
     eng.start_uci().await?;
-    eng.set_option("MultiPV".to_string(), lines).await?;
-    for opt in options {
-        let name = opt.split('=').next().unwrap();
-        let value = opt.split('=').nth(1).unwrap();
-        eng.set_option(name.to_string(), value.to_string()).await?;
+    println!("Using engine: {} ({path})", eng.info());
+    if lines > 1 {
+        eng.set_spin("MultiPV", lines as isize).await?;
+    }
+    for (name, value) in options {
+        eng.set_option(name, value).await?;
     }
     eng.new_game().await?;
-    eng.set_position(&fen).await?;
     Ok(eng)
 }
 
-async fn stream_engine_eval(engine: &mut Engine, show_moves: bool) -> Result<()> {
-    let mut last_eval = Evaluation::default();
+/// Print the evaluation updates of a search until the engine sends its best move. Ctrl-C sends
+/// `ponderhit` to a pondering engine, and stops the search otherwise.
+async fn stream_engine_eval(
+    engine: &mut Engine,
+    mut events: BroadcastStream<UCI>,
+    moves: Option<Notation>,
+    mut pondering: bool,
+) -> Result<()> {
+    let mut last_lines = Vec::new();
+    let mut stopped = false;
+    // a single listener for the whole search, as in `analyze`
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
     loop {
-        if let Some(ev) = engine.get_evaluation().await {
-            if ev != last_eval {
-                if show_moves {
-                    println!("{ev:#}");
+        let msg = tokio::select! {
+            biased;
+            res = &mut ctrl_c, if !stopped => {
+                res?;
+                if pondering {
+                    engine.ponderhit().await?;
+                    pondering = false;
+                    // listen again, to stop a search without limits
+                    ctrl_c.set(tokio::signal::ctrl_c());
                 } else {
-                    println!("{ev:}")
+                    // the best move is still received through the events
+                    engine.stop().await?;
+                    stopped = true;
                 }
-                last_eval = ev;
+                continue;
             }
+            msg = events.next() => match msg {
+                Some(msg) => msg?,
+                None => break,
+            },
+        };
+        match msg {
+            UCI::Info(_) => {
+                let lines = engine.get_lines().await;
+                if lines != last_lines {
+                    print_lines(&lines, moves);
+                    last_lines = lines;
+                }
+            }
+            UCI::BestMove { bestmove, ponder } => {
+                print_best_move(&bestmove, &ponder);
+                break;
+            }
+            _ => continue,
         }
-        yield_now().await;
+    }
+    Ok(())
+}
+
+/// Analyse a position until interrupted with Ctrl-C, printing the best move found so far
+async fn analyze(
+    engine: &mut Engine,
+    position: &Position,
+    params: GoParams,
+    moves: Option<Notation>,
+) -> Result<()> {
+    let mut session = engine.analyze(position, params).await?;
+    let mut last_lines = Vec::new();
+    // a single listener for the whole analysis, so that no Ctrl-C is lost between updates
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+    loop {
+        tokio::select! {
+            // polled first, the handler is in place before the first line is printed
+            biased;
+            res = &mut ctrl_c => {
+                res?;
+                break;
+            }
+            update = session.next() => match update {
+                Some(lines) if lines != last_lines => {
+                    print_lines(&lines, moves);
+                    last_lines = lines;
+                }
+                Some(_) => continue,
+                None => break,
+            },
+        }
+    }
+    let bm = session.stop().await?;
+    print_best_move(&bm.bestmove, &bm.ponder);
+    Ok(())
+}
+
+/// Print the evaluation of every line, with its moves in the given notation if any
+fn print_lines(lines: &[Evaluation], moves: Option<Notation>) {
+    for ev in lines.iter() {
+        match moves {
+            // the alternate form falls back to UCI notation if the pv isn't legal
+            Some(Notation::San) => println!("{ev:#}"),
+            Some(Notation::Uci) => println!("{ev:}\npv: {}", ev.pv.join(", ")),
+            None => println!("{ev:}"),
+        }
+    }
+}
+
+fn print_best_move(bestmove: &str, ponder: &Option<String>) {
+    match ponder {
+        Some(ponder) => println!("bestmove: {bestmove} ponder: {ponder}"),
+        None => println!("bestmove: {bestmove}"),
     }
 }
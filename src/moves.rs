@@ -0,0 +1,532 @@
+use crate::board::{Board, CastlingRights, Color, Piece, PieceKind, Square};
+use std::{fmt::Display, str::FromStr};
+use thiserror::Error;
+
+const KNIGHT_STEPS: [(i8, i8); 8] = [
+    (1, 2),
+    (2, 1),
+    (2, -1),
+    (1, -2),
+    (-1, -2),
+    (-2, -1),
+    (-2, 1),
+    (-1, 2),
+];
+const KING_STEPS: [(i8, i8); 8] = [
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+];
+const BISHOP_RAYS: [(i8, i8); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+const ROOK_RAYS: [(i8, i8); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+const PROMOTIONS: [PieceKind; 4] = [
+    PieceKind::Queen,
+    PieceKind::Rook,
+    PieceKind::Bishop,
+    PieceKind::Knight,
+];
+
+/// Errors produced parsing or playing a move
+#[derive(Error, Debug, PartialEq)]
+pub enum MoveError {
+    /// The move isn't written in long algebraic notation, i.e. `e2e4` or `e7e8q`
+    #[error("invalid move '{0}'")]
+    Notation(String),
+
+    /// The move can't be played in the position
+    #[error("illegal move '{mv}' in position '{fen}'")]
+    Illegal { mv: String, fen: String },
+}
+
+/// Move in the long algebraic notation used by UCI. Castling is written as the king moving two
+/// squares, i.e. `e1g1`.
+///
+/// ```
+/// use async_uci::board::Board;
+/// use async_uci::moves::Move;
+///
+/// let mv: Move = "e7e8q".parse().unwrap();
+/// assert_eq!(mv.to_string(), "e7e8q");
+/// let board = Board::startpos().play("e2e4").unwrap();
+/// assert_eq!(board.legal_moves().len(), 20);
+/// assert!(board.play("e2e4").is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Move {
+    pub from: Square,
+    pub to: Square,
+    /// Piece a pawn is promoted to when reaching the last rank
+    pub promotion: Option<PieceKind>,
+}
+
+impl Move {
+    fn new(from: Square, to: Square) -> Self {
+        Move {
+            from,
+            to,
+            promotion: None,
+        }
+    }
+}
+
+impl FromStr for Move {
+    type Err = MoveError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || MoveError::Notation(s.to_string());
+        if !s.is_ascii() || (s.len() != 4 && s.len() != 5) {
+            return Err(err());
+        }
+        let from = s[0..2].parse().map_err(|_| err())?;
+        let to = s[2..4].parse().map_err(|_| err())?;
+        let promotion = match s[4..].chars().next() {
+            Some(c) => match PieceKind::from_char(c) {
+                Some(kind) if c.is_ascii_lowercase() && PROMOTIONS.contains(&kind) => Some(kind),
+                _ => return Err(err()),
+            },
+            None => None,
+        };
+        Ok(Move {
+            from,
+            to,
+            promotion,
+        })
+    }
+}
+
+impl Display for Move {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("{}{}", self.from, self.to))?;
+        if let Some(kind) = self.promotion {
+            f.write_fmt(format_args!("{}", kind.to_char()))?;
+        }
+        Ok(())
+    }
+}
+
+/// Square reached moving from `square` by a number of files and ranks, if it's on the board
+fn step(square: Square, (files, ranks): (i8, i8)) -> Option<Square> {
+    let file = square.file() as i8 + files;
+    let rank = square.rank() as i8 + ranks;
+    if (0..8).contains(&file) && (0..8).contains(&rank) {
+        Square::new(file as u8, rank as u8)
+    } else {
+        None
+    }
+}
+
+/// Square of a name known to be valid
+fn square(name: &str) -> Square {
+    name.parse().expect("invalid square")
+}
+
+impl Board {
+    /// Moves the side to move can legally play
+    pub fn legal_moves(&self) -> Vec<Move> {
+        let color = self.side_to_move;
+        self.pseudo_legal_moves()
+            .into_iter()
+            .filter(|mv| !self.apply(mv).in_check(color))
+            .collect()
+    }
+
+    /// Check if a move can be played in the position
+    pub fn is_legal(&self, mv: &Move) -> bool {
+        self.legal_moves().contains(mv)
+    }
+
+    /// Parse a move in long algebraic notation and check it's legal in the position
+    pub fn parse_move(&self, mv: &str) -> Result<Move, MoveError> {
+        let parsed: Move = mv.parse()?;
+        if !self.is_legal(&parsed) {
+            return Err(MoveError::Illegal {
+                mv: mv.to_string(),
+                fen: self.to_string(),
+            });
+        }
+        Ok(parsed)
+    }
+
+    /// Board after playing a move given in long algebraic notation
+    pub fn play(&self, mv: &str) -> Result<Board, MoveError> {
+        Ok(self.apply(&self.parse_move(mv)?))
+    }
+
+    /// Board after playing a list of moves in long algebraic notation, failing on the first
+    /// illegal one
+    pub fn play_moves<I, S>(&self, moves: I) -> Result<Board, MoveError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        moves
+            .into_iter()
+            .try_fold(self.clone(), |board, mv| board.play(mv.as_ref()))
+    }
+
+    /// Check if the side to move is in check
+    pub fn is_check(&self) -> bool {
+        self.in_check(self.side_to_move)
+    }
+
+    /// Check if the side to move is in check and has no legal moves
+    pub fn is_checkmate(&self) -> bool {
+        self.is_check() && self.legal_moves().is_empty()
+    }
+
+    /// Count the leaf nodes of the legal move tree up to a depth, to test move generation
+    /// against known results
+    pub fn perft(&self, depth: u32) -> u64 {
+        match depth {
+            0 => 1,
+            1 => self.legal_moves().len() as u64,
+            _ => self
+                .legal_moves()
+                .iter()
+                .map(|mv| self.apply(mv).perft(depth - 1))
+                .sum(),
+        }
+    }
+
+    /// Check if the king of a side is attacked
+    pub(crate) fn in_check(&self, color: Color) -> bool {
+        match self.king(color) {
+            Some(king) => self.is_attacked(king, color.opponent()),
+            None => false,
+        }
+    }
+
+    /// Check if a square is attacked by any piece of a side
+    fn is_attacked(&self, target: Square, by: Color) -> bool {
+        let is = |square: Option<Square>, kinds: &[PieceKind]| {
+            square
+                .and_then(|sq| self.piece_at(sq))
+                .is_some_and(|p| p.color == by && kinds.contains(&p.kind))
+        };
+        // pawns attack forward, so they're found diagonally behind the target
+        let behind = match by {
+            Color::White => -1,
+            Color::Black => 1,
+        };
+        if [(-1, behind), (1, behind)]
+            .into_iter()
+            .any(|s| is(step(target, s), &[PieceKind::Pawn]))
+            || KNIGHT_STEPS
+                .into_iter()
+                .any(|s| is(step(target, s), &[PieceKind::Knight]))
+            || KING_STEPS
+                .into_iter()
+                .any(|s| is(step(target, s), &[PieceKind::King]))
+        {
+            return true;
+        }
+        let sliders = [
+            (BISHOP_RAYS, [PieceKind::Bishop, PieceKind::Queen]),
+            (ROOK_RAYS, [PieceKind::Rook, PieceKind::Queen]),
+        ];
+        sliders.iter().any(|(rays, kinds)| {
+            rays.iter()
+                .any(|ray| is(self.ray(target, *ray).last(), kinds))
+        })
+    }
+
+    /// Squares from `from` in a direction, up to and including the first occupied one
+    fn ray(&self, from: Square, direction: (i8, i8)) -> impl Iterator<Item = Square> + '_ {
+        let mut next = Some(from);
+        let mut blocked = false;
+        std::iter::from_fn(move || {
+            if blocked {
+                return None;
+            }
+            next = step(next?, direction);
+            let square = next?;
+            blocked = self.piece_at(square).is_some();
+            Some(square)
+        })
+    }
+
+    /// Moves following how pieces move, which may leave the own king in check
+    fn pseudo_legal_moves(&self) -> Vec<Move> {
+        let color = self.side_to_move;
+        let mut moves = Vec::new();
+        let empty = |sq: Square| self.piece_at(sq).is_none();
+        let enemy = |sq: Square| self.piece_at(sq).is_some_and(|p| p.color != color);
+        for from in (0..64).map(Square) {
+            let kind = match self.piece_at(from) {
+                Some(piece) if piece.color == color => piece.kind,
+                _ => continue,
+            };
+            let mut targets = Vec::new();
+            match kind {
+                PieceKind::Pawn => {
+                    self.pawn_moves(from, &mut moves);
+                    continue;
+                }
+                PieceKind::Knight | PieceKind::King => {
+                    let steps = match kind {
+                        PieceKind::Knight => KNIGHT_STEPS,
+                        _ => KING_STEPS,
+                    };
+                    targets.extend(steps.into_iter().filter_map(|s| step(from, s)));
+                }
+                PieceKind::Bishop | PieceKind::Rook | PieceKind::Queen => {
+                    let rays: &[(i8, i8)] = match kind {
+                        PieceKind::Bishop => &BISHOP_RAYS,
+                        PieceKind::Rook => &ROOK_RAYS,
+                        _ => &KING_STEPS,
+                    };
+                    for ray in rays {
+                        targets.extend(self.ray(from, *ray));
+                    }
+                }
+            }
+            moves.extend(
+                targets
+                    .into_iter()
+                    .filter(|to| empty(*to) || enemy(*to))
+                    .map(|to| Move::new(from, to)),
+            );
+        }
+        self.castling_moves(&mut moves);
+        moves
+    }
+
+    /// Pushes and captures of a pawn, with every promotion when reaching the last rank
+    fn pawn_moves(&self, from: Square, moves: &mut Vec<Move>) {
+        let color = self.side_to_move;
+        let (forward, start_rank, last_rank) = match color {
+            Color::White => (1, 1, 7),
+            Color::Black => (-1, 6, 0),
+        };
+        let mut targets = Vec::new();
+        if let Some(to) = step(from, (0, forward)).filter(|sq| self.piece_at(*sq).is_none()) {
+            targets.push(to);
+            let double = step(to, (0, forward)).filter(|sq| self.piece_at(*sq).is_none());
+            if let (true, Some(to)) = (from.rank() == start_rank, double) {
+                targets.push(to);
+            }
+        }
+        for side in [-1, 1] {
+            let capture = step(from, (side, forward)).filter(|sq| {
+                self.en_passant == Some(*sq) || self.piece_at(*sq).is_some_and(|p| p.color != color)
+            });
+            targets.extend(capture);
+        }
+        for to in targets {
+            if to.rank() == last_rank {
+                moves.extend(PROMOTIONS.into_iter().map(|kind| Move {
+                    from,
+                    to,
+                    promotion: Some(kind),
+                }));
+            } else {
+                moves.push(Move::new(from, to));
+            }
+        }
+    }
+
+    /// Castling moves whose squares between the king and rook are empty and whose king
+    /// doesn't pass through or start on an attacked square
+    fn castling_moves(&self, moves: &mut Vec<Move>) {
+        let color = self.side_to_move;
+        let sides = match color {
+            Color::White => [
+                (
+                    self.castling.white_king,
+                    "e1",
+                    "g1",
+                    ["f1", "g1"].as_slice(),
+                ),
+                (
+                    self.castling.white_queen,
+                    "e1",
+                    "c1",
+                    ["d1", "c1", "b1"].as_slice(),
+                ),
+            ],
+            Color::Black => [
+                (
+                    self.castling.black_king,
+                    "e8",
+                    "g8",
+                    ["f8", "g8"].as_slice(),
+                ),
+                (
+                    self.castling.black_queen,
+                    "e8",
+                    "c8",
+                    ["d8", "c8", "b8"].as_slice(),
+                ),
+            ],
+        };
+        for (allowed, king, to, between) in sides {
+            let (king, to) = (square(king), square(to));
+            let between: Vec<Square> = between.iter().map(|sq| square(sq)).collect();
+            // the king crosses the first two squares, the rook also the third one
+            let possible = allowed
+                && between.iter().all(|sq| self.piece_at(*sq).is_none())
+                && !self.is_attacked(king, color.opponent())
+                && !between[..2]
+                    .iter()
+                    .any(|sq| self.is_attacked(*sq, color.opponent()));
+            if possible {
+                moves.push(Move::new(king, to));
+            }
+        }
+    }
+
+    /// Board after playing a move, which must be pseudo-legal in the position
+    pub(crate) fn apply(&self, mv: &Move) -> Board {
+        let mut board = self.clone();
+        let piece = self.piece_at(mv.from).expect("no piece to move");
+        let captured = self.piece_at(mv.to);
+        board.squares[mv.from.index()] = None;
+        board.squares[mv.to.index()] = Some(Piece {
+            color: piece.color,
+            kind: mv.promotion.unwrap_or(piece.kind),
+        });
+
+        if piece.kind == PieceKind::Pawn && Some(mv.to) == self.en_passant {
+            let taken = Square::new(mv.to.file(), mv.from.rank()).expect("invalid square");
+            board.squares[taken.index()] = None;
+        }
+        if piece.kind == PieceKind::King && mv.from.file().abs_diff(mv.to.file()) == 2 {
+            let (rook_file, rook_to) = match mv.to.file() {
+                6 => (7, 5),
+                _ => (0, 3),
+            };
+            let rank = mv.from.rank();
+            let rook = Square::new(rook_file, rank).expect("invalid square");
+            let rook_to = Square::new(rook_to, rank).expect("invalid square");
+            board.squares[rook_to.index()] = board.squares[rook.index()].take();
+        }
+
+        board.castling = self.castling_after(mv);
+        board.en_passant =
+            if piece.kind == PieceKind::Pawn && mv.from.rank().abs_diff(mv.to.rank()) == 2 {
+                Square::new(mv.from.file(), (mv.from.rank() + mv.to.rank()) / 2)
+            } else {
+                None
+            };
+        board.halfmove_clock = if piece.kind == PieceKind::Pawn || captured.is_some() {
+            0
+        } else {
+            self.halfmove_clock + 1
+        };
+        if piece.color == Color::Black {
+            board.fullmove_number += 1;
+        }
+        board.side_to_move = piece.color.opponent();
+        board
+    }
+
+    /// Castling rights left once a king or rook moves, or a rook is captured
+    fn castling_after(&self, mv: &Move) -> CastlingRights {
+        let mut rights = self.castling;
+        for sq in [mv.from, mv.to] {
+            match sq.to_string().as_str() {
+                "e1" => (rights.white_king, rights.white_queen) = (false, false),
+                "e8" => (rights.black_king, rights.black_queen) = (false, false),
+                "h1" => rights.white_king = false,
+                "a1" => rights.white_queen = false,
+                "h8" => rights.black_king = false,
+                "a8" => rights.black_queen = false,
+                _ => (),
+            }
+        }
+        rights
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::board::{Board, PieceKind, STARTPOS_FEN};
+    use crate::moves::{Move, MoveError};
+
+    #[test]
+    fn test_move_notation() {
+        let mv: Move = "a7a8n".parse().unwrap();
+        assert_eq!(mv.promotion, Some(PieceKind::Knight));
+        assert_eq!(mv.to_string(), "a7a8n");
+        for invalid in ["e2", "e2e9", "e7e8k", "e7e8Q", "0000", "e2e4 "] {
+            assert_eq!(
+                invalid.parse::<Move>(),
+                Err(MoveError::Notation(invalid.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_play() {
+        let board = Board::startpos()
+            .play_moves(["e2e4", "d7d5", "e4e5", "f7f5"])
+            .unwrap();
+        assert_eq!(
+            board.to_string(),
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3"
+        );
+        let board = board.play("e5f6").unwrap();
+        assert!(board.piece_at("f5".parse().unwrap()).is_none());
+        assert_eq!(
+            Board::startpos().play("e1g1"),
+            Err(MoveError::Illegal {
+                mv: "e1g1".to_string(),
+                fen: STARTPOS_FEN.to_string()
+            })
+        );
+
+        // castling moves the rook and loses the rights of the side
+        let board: Board = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1".parse().unwrap();
+        let castled = board.play("e1c1").unwrap();
+        assert_eq!(castled.to_string(), "r3k2r/8/8/8/8/8/8/2KR3R b kq - 1 1");
+        let castled = castled.play("h8h1").unwrap();
+        assert_eq!(castled.to_string(), "r3k3/8/8/8/8/8/8/2KR3r w q - 0 2");
+
+        let mate = Board::startpos()
+            .play_moves(["f2f3", "e7e5", "g2g4", "d8h4"])
+            .unwrap();
+        assert!(mate.is_checkmate());
+        assert!(mate.legal_moves().is_empty());
+    }
+
+    #[test]
+    fn test_perft() {
+        // known leaf counts from https://www.chessprogramming.org/Perft_Results
+        let positions = [
+            (STARTPOS_FEN, vec![20, 400, 8902]),
+            (
+                "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+                vec![48, 2039],
+            ),
+            (
+                "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+                vec![14, 191, 2812],
+            ),
+            (
+                "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+                vec![6, 264, 9467],
+            ),
+            (
+                "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+                vec![44, 1486],
+            ),
+        ];
+        for (fen, counts) in positions {
+            let board: Board = fen.parse().unwrap();
+            for (depth, count) in counts.into_iter().enumerate() {
+                assert_eq!(
+                    board.perft(depth as u32 + 1),
+                    count,
+                    "{} {}",
+                    fen,
+                    depth + 1
+                );
+            }
+        }
+    }
+}
@@ -1,10 +1,17 @@
-use anyhow::Result;
 use std::{collections::HashMap, fmt::Display, str::FromStr};
 use thiserror::Error;
 
 /// Supported UCI commands
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
+// info lines are by far the most common message, boxing them would only add allocations
+#[allow(clippy::large_enum_variant)]
 pub enum UCI {
+    /// Engine identification sent after the 'uci' command, one field per line
+    Id {
+        name: Option<String>,
+        author: Option<String>,
+    },
+
     /// Sent after the 'uci' command
     UciOk,
 
@@ -12,21 +19,101 @@ pub enum UCI {
     ReadyOk,
 
     /// Engine sending info to GUI
-    Info {
-        cp: Option<isize>,
-        mate: Option<isize>,
-        depth: Option<isize>,
-        seldepth: Option<isize>,
-        nodes: Option<isize>,
-        time: Option<isize>,
-        multipv: Option<isize>,
-        pv: Option<Vec<String>>,
-    },
+    Info(Info),
 
     /// Options can be set to modify the engine behaviour
     Option { name: String, opt_type: OptionType },
+
+    /// Sent when the engine finishes a search, optionally with the move it expects in reply
+    BestMove {
+        bestmove: String,
+        ponder: Option<String>,
+    },
+
+    /// Result of the engine checking its copy protection, sent after the 'uci' command
+    CopyProtection(CheckStatus),
+
+    /// Result of the engine checking its registration, sent after the 'uci' command and after
+    /// every 'register' command
+    Registration(CheckStatus),
+
+    /// Line sent by the engine that couldn't be parsed, kept as is
+    Unknown(String),
 }
 
+/// Progress of a copy protection or registration check
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum CheckStatus {
+    Checking,
+    Ok,
+    Error,
+}
+
+/// Search information sent by the engine, every field is optional
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct Info {
+    pub cp: Option<isize>,
+    pub mate: Option<isize>,
+    /// The score is only a lower bound of the real score
+    pub lowerbound: bool,
+    /// The score is only an upper bound of the real score
+    pub upperbound: bool,
+    pub wdl: Option<Wdl>,
+    pub depth: Option<isize>,
+    pub seldepth: Option<isize>,
+    pub nodes: Option<isize>,
+    pub time: Option<isize>,
+    pub multipv: Option<isize>,
+    pub pv: Option<Vec<String>>,
+    pub nps: Option<isize>,
+    pub hashfull: Option<isize>,
+    pub tbhits: Option<isize>,
+    pub sbhits: Option<isize>,
+    pub cpuload: Option<isize>,
+    pub currmove: Option<String>,
+    pub currmovenumber: Option<isize>,
+    pub refutation: Option<Vec<String>>,
+    /// CPU number the `currline` belongs to
+    pub cpunr: Option<isize>,
+    pub currline: Option<Vec<String>>,
+    /// Free text sent by the engine, spans until the end of the line
+    pub string: Option<String>,
+}
+
+/// Win, draw and loss expectation in permille, sent when `UCI_ShowWDL` is enabled
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub struct Wdl {
+    pub win: isize,
+    pub draw: isize,
+    pub loss: isize,
+}
+
+/// Keywords that may appear in an info line, used to know where move lists end
+const INFO_KEYWORDS: [&str; 22] = [
+    "depth",
+    "seldepth",
+    "time",
+    "nodes",
+    "pv",
+    "multipv",
+    "score",
+    "cp",
+    "mate",
+    "lowerbound",
+    "upperbound",
+    "wdl",
+    "currmove",
+    "currmovenumber",
+    "refutation",
+    "currline",
+    "hashfull",
+    "nps",
+    "tbhits",
+    "sbhits",
+    "cpuload",
+    "string",
+];
+
 /// Possible types for Engine Options
 #[derive(PartialEq, Debug, Clone)]
 pub enum OptionType {
@@ -49,49 +136,49 @@ pub enum OptionType {
 }
 
 impl OptionType {
-    fn new(opt_type: String, line: String) -> Result<Self> {
+    fn new(line: &str, fields: &OptionFields) -> Result<Self> {
+        let (position, opt_type) = option_field(fields, "type").ok_or(UCIError::ParseError {
+            line: line.to_string(),
+            position: line.len(),
+        })?;
         Ok(match opt_type.as_str() {
-            "check" => OptionType::new_check(line)?,
-            "spin" => OptionType::new_spin(line)?,
-            "combo" => OptionType::new_combo(line)?,
+            "check" => OptionType::new_check(line, fields)?,
+            "spin" => OptionType::new_spin(line, fields)?,
+            "combo" => OptionType::new_combo(fields)?,
             "button" => OptionType::new_button()?,
-            "string" => OptionType::new_string(line)?,
-            _ => return Err(UCIError::ParseError.into()),
+            "string" => OptionType::new_string(fields)?,
+            _ => {
+                return Err(UCIError::ParseError {
+                    line: line.to_string(),
+                    position,
+                })
+            }
         })
     }
 
-    fn new_check(line: String) -> Result<Self> {
-        let words = vec!["default"];
-        let values = parse_line_values(line, words)?;
+    fn new_check(line: &str, fields: &OptionFields) -> Result<Self> {
         Ok(OptionType::Check {
-            default: values["default"].unwrap(),
+            default: parse_option_field(line, fields, "default")?,
         })
     }
 
-    fn new_spin(line: String) -> Result<Self> {
-        let words = vec!["default", "min", "max"];
-        let values = parse_line_values(line, words)?;
+    fn new_spin(line: &str, fields: &OptionFields) -> Result<Self> {
         Ok(OptionType::Spin {
-            default: values["default"].unwrap(),
-            min: values["min"].unwrap(),
-            max: values["max"].unwrap(),
+            default: parse_option_field(line, fields, "default")?,
+            min: parse_option_field(line, fields, "min")?,
+            max: parse_option_field(line, fields, "max")?,
         })
     }
 
-    fn new_combo(line: String) -> Result<Self> {
-        let words = vec!["default"];
-        let values = parse_line_values(line.clone(), words)?;
-        let line: Vec<&str> = line.split_whitespace().collect();
-        let mut options = Vec::new();
-        // TODO: Check if combo options can have spaces, in which case this will give incorrect results
-        for ix in 0..line.len() {
-            if line[ix] == "var" {
-                options.push(line[ix + 1].to_string());
-            }
-        }
+    fn new_combo(fields: &OptionFields) -> Result<Self> {
         Ok(OptionType::Combo {
-            default: values["default"].clone().unwrap(),
-            options: options,
+            default: option_field(fields, "default")
+                .map(|(_, v)| v)
+                .unwrap_or_default(),
+            options: fields
+                .get("var")
+                .map(|vars| vars.iter().map(|(_, v)| v.clone()).collect())
+                .unwrap_or_default(),
         })
     }
 
@@ -99,112 +186,293 @@ impl OptionType {
         Ok(OptionType::Button)
     }
 
-    fn new_string(line: String) -> Result<Self> {
-        let words = vec!["default"];
-        let values = parse_line_values(line, words)?;
+    fn new_string(fields: &OptionFields) -> Result<Self> {
         Ok(OptionType::String {
-            default: values["default"].clone().unwrap(),
+            default: option_field(fields, "default")
+                .map(|(_, v)| v)
+                .unwrap_or_default(),
         })
     }
 }
 
 /// Errors produced from UCI parsing
-#[derive(Error, Debug)]
+#[derive(Error, Debug, PartialEq)]
 pub enum UCIError {
     /// Error parsing a UCI command
-    ParseError,
+    ParseError {
+        /// Line that couldn't be parsed, without surrounding whitespace
+        line: String,
+        /// Byte offset in the line where parsing failed
+        position: usize,
+    },
 }
 
 impl Display for UCIError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let data = match self {
-            UCIError::ParseError => "error parsing uci command",
-        };
-        return f.write_str(data);
+        match self {
+            UCIError::ParseError { line, position } => f.write_fmt(format_args!(
+                "error parsing uci command at position {}: '{}'",
+                position, line
+            )),
+        }
     }
 }
 
+/// Result of parsing an UCI command
+pub type Result<T> = std::result::Result<T, UCIError>;
+
 /// Parse an UCI command
 pub fn parse_uci(line: String) -> Result<UCI> {
     let line = line.trim().to_string();
     let command = line.split_whitespace().next().unwrap_or("");
     match command {
+        "id" => parse_id_line(line),
         "info" => parse_info_line(line),
         "uciok" => Ok(UCI::UciOk),
         "readyok" => Ok(UCI::ReadyOk),
         "option" => parse_option_line(line),
-        _ => Err(UCIError::ParseError.into()),
+        "bestmove" => parse_bestmove_line(line),
+        "copyprotection" => Ok(UCI::CopyProtection(parse_check_status(line)?)),
+        "registration" => Ok(UCI::Registration(parse_check_status(line)?)),
+        _ => Err(UCIError::ParseError { line, position: 0 }),
     }
 }
 
-/// parse_line_values parses the value following each word in the given line.
-fn parse_line_values<T: FromStr + Default>(
-    line: String,
-    words: Vec<&str>,
-) -> Result<HashMap<String, Option<T>>> {
-    let line: Vec<&str> = line.split_whitespace().collect();
-    let mut values = HashMap::with_capacity(words.len());
-    for word in words.iter() {
-        let mut i = line.iter();
-        let value = match i.position(|x: &&str| x == word) {
-            Some(ix) => match line.get(ix + 1) {
-                Some(v) => v.parse::<T>().ok(),
-                None => Some(T::default()),
-            },
-            None => None,
-        };
-        values.insert(word.to_string(), value);
-    }
-    Ok(values)
+/// Split a line into whitespace separated tokens along with their byte offset in the line
+fn tokenize(line: &str) -> Vec<(usize, &str)> {
+    line.split_whitespace()
+        .map(|token| (token.as_ptr() as usize - line.as_ptr() as usize, token))
+        .collect()
 }
 
 /// Parse an info line for all supported metadata
 fn parse_info_line(line: String) -> Result<UCI> {
-    let words = vec![
-        "cp", "depth", "nodes", "seldepth", "mate", "time", "multipv",
-    ];
-    let values = parse_line_values(line.clone(), words)?;
-    return Ok(UCI::Info {
-        cp: values["cp"],
-        mate: values["mate"],
-        depth: values["depth"],
-        nodes: values["nodes"],
-        time: values["time"],
-        multipv: values["multipv"],
-        seldepth: values["seldepth"],
-        pv: parse_pv(line),
-    });
+    let offsets = tokenize(&line);
+    let tokens: Vec<&str> = offsets.iter().map(|(_, token)| *token).collect();
+    let mut info = Info::default();
+    let mut ix = 1; // skip 'info'
+    while ix < tokens.len() {
+        let keyword = tokens[ix];
+        ix += 1;
+        match keyword {
+            "depth" => info.depth = parse_number(&tokens, &mut ix),
+            "seldepth" => info.seldepth = parse_number(&tokens, &mut ix),
+            "time" => info.time = parse_number(&tokens, &mut ix),
+            "nodes" => info.nodes = parse_number(&tokens, &mut ix),
+            "multipv" => info.multipv = parse_number(&tokens, &mut ix),
+            "cp" => info.cp = parse_number(&tokens, &mut ix),
+            "mate" => info.mate = parse_number(&tokens, &mut ix),
+            "lowerbound" => info.lowerbound = true,
+            "upperbound" => info.upperbound = true,
+            "currmovenumber" => info.currmovenumber = parse_number(&tokens, &mut ix),
+            "hashfull" => info.hashfull = parse_number(&tokens, &mut ix),
+            "nps" => info.nps = parse_number(&tokens, &mut ix),
+            "tbhits" => info.tbhits = parse_number(&tokens, &mut ix),
+            "sbhits" => info.sbhits = parse_number(&tokens, &mut ix),
+            "cpuload" => info.cpuload = parse_number(&tokens, &mut ix),
+            "currmove" => {
+                info.currmove = tokens.get(ix).map(|m| m.to_string());
+                ix += 1;
+            }
+            "wdl" => {
+                info.wdl = match (
+                    parse_number(&tokens, &mut ix),
+                    parse_number(&tokens, &mut ix),
+                    parse_number(&tokens, &mut ix),
+                ) {
+                    (Some(win), Some(draw), Some(loss)) => Some(Wdl { win, draw, loss }),
+                    _ => None,
+                }
+            }
+            "pv" => info.pv = Some(parse_moves(&tokens, &mut ix)),
+            "refutation" => info.refutation = Some(parse_moves(&tokens, &mut ix)),
+            "currline" => {
+                // the cpu number is optional, moves never parse as numbers
+                if let Some(Ok(cpunr)) = tokens.get(ix).map(|t| t.parse::<isize>()) {
+                    info.cpunr = Some(cpunr);
+                    ix += 1;
+                }
+                info.currline = Some(parse_moves(&tokens, &mut ix));
+            }
+            "string" => {
+                // the rest of the line is free text, kept as sent
+                let text = offsets.get(ix).map(|(offset, _)| &line[*offset..]);
+                info.string = Some(text.unwrap_or_default().to_string());
+                break;
+            }
+            // 'score' only groups the following score tokens, unknown tokens are ignored
+            _ => continue,
+        }
+    }
+    Ok(UCI::Info(info))
 }
 
-/// Parse an info line and return all the moves stated after 'pv'
-fn parse_pv(line: String) -> Option<Vec<String>> {
-    let line: Vec<&str> = line.split_whitespace().collect();
-    let mut pv = Vec::new();
-    let mut i = line.iter();
-    match i.position(|x: &&str| *x == "pv") {
-        Some(_) => {}
-        None => return None, // early return if no pv is found
-    };
-    while let Some(word) = i.next() {
-        pv.push(word.to_string());
+/// Parse the number at the current position and advance past it
+fn parse_number<T: FromStr>(tokens: &[&str], ix: &mut usize) -> Option<T> {
+    let value = tokens.get(*ix).and_then(|v| v.parse::<T>().ok());
+    *ix += 1;
+    value
+}
+
+/// Parse moves from the current position until the next info keyword
+fn parse_moves(tokens: &[&str], ix: &mut usize) -> Vec<String> {
+    let mut moves = Vec::new();
+    while let Some(token) = tokens.get(*ix) {
+        if INFO_KEYWORDS.contains(token) {
+            break;
+        }
+        moves.push(token.to_string());
+        *ix += 1;
     }
-    Some(pv)
+    moves
 }
 
+/// Values of an option line and their offset in the line, grouped by the keyword preceding them
+type OptionFields = HashMap<&'static str, Vec<(usize, String)>>;
+
+/// Keywords that delimit the values of an option line
+const OPTION_KEYWORDS: [&str; 6] = ["name", "type", "default", "min", "max", "var"];
+
+/// Parse an option line, i.e. `option name Clear Hash type button`
 fn parse_option_line(line: String) -> Result<UCI> {
-    // FIXME: handle `name`s with spaces (i.e. `option name Clear Hash type button`)
-    let words = vec!["name", "type"];
-    let values = parse_line_values(line.clone(), words)?;
-    return Ok(UCI::Option {
-        name: values["name"].clone().unwrap(),
-        opt_type: OptionType::new(values["type"].clone().unwrap(), line)?,
-    });
+    let fields = parse_option_fields(&line);
+    let (_, name) = option_field(&fields, "name").ok_or(UCIError::ParseError {
+        line: line.clone(),
+        position: line.len(),
+    })?;
+    Ok(UCI::Option {
+        name,
+        opt_type: OptionType::new(&line, &fields)?,
+    })
+}
+
+/// Split an option line into the values following each keyword. Values span every token up to
+/// the next keyword, so names and values may contain spaces. `<empty>` is read as an empty value.
+fn parse_option_fields(line: &str) -> OptionFields {
+    let mut fields = OptionFields::new();
+    let mut current: Option<(&'static str, usize, Vec<&str>)> = None;
+    for (offset, token) in tokenize(line).into_iter().skip(1) {
+        match OPTION_KEYWORDS.iter().find(|k| **k == token) {
+            Some(keyword) => {
+                let next = (*keyword, offset + token.len(), Vec::new());
+                if let Some(field) = current.replace(next) {
+                    push_option_field(&mut fields, field);
+                }
+            }
+            None => {
+                if let Some((_, start, value)) = current.as_mut() {
+                    if value.is_empty() {
+                        *start = offset;
+                    }
+                    value.push(token);
+                }
+            }
+        }
+    }
+    if let Some(field) = current {
+        push_option_field(&mut fields, field);
+    }
+    fields
+}
+
+fn push_option_field(
+    fields: &mut OptionFields,
+    (key, offset, value): (&'static str, usize, Vec<&str>),
+) {
+    let value = match value.join(" ") {
+        v if v == "<empty>" => String::new(),
+        v => v,
+    };
+    fields.entry(key).or_default().push((offset, value));
+}
+
+/// Get the first value given for a keyword in an option line, along with its offset
+fn option_field(fields: &OptionFields, key: &str) -> Option<(usize, String)> {
+    fields.get(key).and_then(|values| values.first()).cloned()
+}
+
+/// Get and parse the first value given for a keyword in an option line
+fn parse_option_field<T: FromStr>(line: &str, fields: &OptionFields, key: &str) -> Result<T> {
+    match option_field(fields, key) {
+        Some((position, value)) => value.parse::<T>().map_err(|_| UCIError::ParseError {
+            line: line.to_string(),
+            position,
+        }),
+        None => Err(UCIError::ParseError {
+            line: line.to_string(),
+            position: line.len(),
+        }),
+    }
+}
+
+/// Parse an id line, i.e. `id name Stockfish 15` or `id author the Stockfish developers`
+fn parse_id_line(line: String) -> Result<UCI> {
+    let tokens = tokenize(&line);
+    let value = match tokens.get(2) {
+        Some((offset, _)) => line[*offset..].to_string(),
+        None => {
+            return Err(UCIError::ParseError {
+                position: line.len(),
+                line,
+            })
+        }
+    };
+    match tokens[1] {
+        (_, "name") => Ok(UCI::Id {
+            name: Some(value),
+            author: None,
+        }),
+        (_, "author") => Ok(UCI::Id {
+            name: None,
+            author: Some(value),
+        }),
+        (position, _) => Err(UCIError::ParseError { line, position }),
+    }
+}
+
+/// Parse a bestmove line, i.e. `bestmove <m1> [ponder <m2>]`
+fn parse_bestmove_line(line: String) -> Result<UCI> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let bestmove = match words.get(1) {
+        Some(m) => m.to_string(),
+        None => {
+            return Err(UCIError::ParseError {
+                position: line.len(),
+                line,
+            })
+        }
+    };
+    let ponder = match (words.get(2), words.get(3)) {
+        (Some(&"ponder"), Some(m)) => Some(m.to_string()),
+        _ => None,
+    };
+    Ok(UCI::BestMove { bestmove, ponder })
+}
+
+/// Parse the status of a check line, i.e. `copyprotection ok` or `registration error`
+fn parse_check_status(line: String) -> Result<CheckStatus> {
+    let tokens = tokenize(&line);
+    match tokens.get(1) {
+        Some((_, "checking")) => Ok(CheckStatus::Checking),
+        Some((_, "ok")) => Ok(CheckStatus::Ok),
+        Some((_, "error")) => Ok(CheckStatus::Error),
+        Some((position, _)) => Err(UCIError::ParseError {
+            position: *position,
+            line,
+        }),
+        None => Err(UCIError::ParseError {
+            position: line.len(),
+            line,
+        }),
+    }
 }
 
 #[cfg(test)]
 mod test {
 
-    use crate::parse::{parse_info_line, UCI};
+    use crate::parse::{
+        parse_info_line, parse_uci, CheckStatus, Info, OptionType, UCIError, Wdl, UCI,
+    };
     use anyhow::Result;
 
     macro_rules! test_info_line {
@@ -217,52 +485,62 @@ mod test {
     #[tokio::test]
     async fn test_parse_info_line() -> Result<()> {
         test_info_line!("info depth 1 seldepth 1 multipv 1 score cp 59 nodes 56 nps 56000 hashfull 0 tbhits 0 time 1", 
-            UCI::Info {
+            UCI::Info(Info {
                 cp: Some(59),
-                mate: None,
                 depth: Some(1),
                 nodes: Some(56),
                 seldepth: Some(1),
                 multipv: Some(1),
                 time: Some(1),
-                pv: None,
-            }
+                nps: Some(56000),
+                hashfull: Some(0),
+                tbhits: Some(0),
+                ..Default::default()
+            })
         );
         test_info_line!("info depth 1 seldepth 1 multipv 1 score cp 59 nodes 56 nps 56000 hashfull 0 tbhits 0 time 1 pv d6f4 e3f4", 
-            UCI::Info {
+            UCI::Info(Info {
                 cp: Some(59),
-                mate: None,
                 depth: Some(1),
                 nodes: Some(56),
                 seldepth: Some(1),
                 multipv: Some(1),
                 time: Some(1),
+                nps: Some(56000),
+                hashfull: Some(0),
+                tbhits: Some(0),
                 pv: Some(vec!["d6f4".to_string(), "e3f4".to_string()]),
-            }
+                ..Default::default()
+            })
         );
         test_info_line!(
             "info depth 2 seldepth 2 multipv 1 score cp -27 nodes 227 nps 227000 hashfull 0 tbhits 0 time 1 pv a8b8 f4d6",
-            UCI::Info {
+            UCI::Info(Info {
                 cp: Some(-27),
-                mate: None,
                 depth: Some(2),
                 nodes: Some(227),
                 seldepth: Some(2),
                 multipv: Some(1),
                 time: Some(1),
+                nps: Some(227000),
+                hashfull: Some(0),
+                tbhits: Some(0),
                 pv: Some(vec!["a8b8".to_string(), "f4d6".to_string()]),
-            }
+                ..Default::default()
+            })
         );
         test_info_line!(
             "info depth 24 seldepth 33 multipv 1 score cp -195 nodes 2499457 nps 642203 hashfull 812 tbhits 0 time 3892 pv d8a5 a4a5 c6a5 f4d6 b7a6 d6c5 f6d7 c5a3 f7f6 e1g1 a8c8 b2b3 e8f7 f1c1 d7b6 f3e1 f5g6 f2f3 h8d8 e3e4 a5c6 e1d3 e6e5 d3c5 d5e4 d2e4 g6e4 c5e4",
-            UCI::Info {
+            UCI::Info(Info {
                 cp: Some(-195),
-                mate: None,
                 depth: Some(24),
                 nodes: Some(2499457),
                 seldepth: Some(33),
                 multipv: Some(1),
                 time: Some(3892),
+                nps: Some(642203),
+                hashfull: Some(812),
+                tbhits: Some(0),
                 pv: Some(vec![
                     "d8a5".to_string(),
                     "a4a5".to_string(),
@@ -293,8 +571,302 @@ mod test {
                     "g6e4".to_string(),
                     "c5e4".to_string(),
                 ]),
+                ..Default::default()
+            })
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_parse_info_line_extra_fields() -> Result<()> {
+        test_info_line!(
+            "info depth 20 seldepth 28 multipv 1 score mate -4 upperbound wdl 0 12 988 nodes 12000 sbhits 3 cpuload 950 time 50 pv e2e4 e7e5",
+            UCI::Info(Info {
+                mate: Some(-4),
+                upperbound: true,
+                wdl: Some(Wdl { win: 0, draw: 12, loss: 988 }),
+                depth: Some(20),
+                seldepth: Some(28),
+                multipv: Some(1),
+                nodes: Some(12000),
+                sbhits: Some(3),
+                cpuload: Some(950),
+                time: Some(50),
+                pv: Some(vec!["e2e4".to_string(), "e7e5".to_string()]),
+                ..Default::default()
+            })
+        );
+        test_info_line!(
+            "info depth 5 currmove e2e4 currmovenumber 1 score cp 20 lowerbound",
+            UCI::Info(Info {
+                depth: Some(5),
+                currmove: Some("e2e4".to_string()),
+                currmovenumber: Some(1),
+                cp: Some(20),
+                lowerbound: true,
+                ..Default::default()
+            })
+        );
+        test_info_line!(
+            "info refutation d1h5 g6h5 currline 1 e2e4 e7e5 string depth 3 pv means nothing here",
+            UCI::Info(Info {
+                refutation: Some(vec!["d1h5".to_string(), "g6h5".to_string()]),
+                cpunr: Some(1),
+                currline: Some(vec!["e2e4".to_string(), "e7e5".to_string()]),
+                string: Some("depth 3 pv means nothing here".to_string()),
+                ..Default::default()
+            })
+        );
+        test_info_line!(
+            "info string NNUE  evaluation\tusing nn-5af11540bbfe.nnue",
+            UCI::Info(Info {
+                string: Some("NNUE  evaluation\tusing nn-5af11540bbfe.nnue".to_string()),
+                ..Default::default()
+            })
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_parse_bestmove_line() -> Result<()> {
+        assert_eq!(
+            parse_uci("bestmove d8a5 ponder a4a5\n".to_string())?,
+            UCI::BestMove {
+                bestmove: "d8a5".to_string(),
+                ponder: Some("a4a5".to_string()),
             }
         );
+        assert_eq!(
+            parse_uci("bestmove e2e4".to_string())?,
+            UCI::BestMove {
+                bestmove: "e2e4".to_string(),
+                ponder: None,
+            }
+        );
+        assert!(parse_uci("bestmove".to_string()).is_err());
+        Ok(())
+    }
+
+    macro_rules! test_option_line {
+        ($line:expr, $name:expr, $opt_type:expr) => {
+            let opt = parse_uci($line.to_string())?;
+            assert_eq!(
+                opt,
+                UCI::Option {
+                    name: $name.to_string(),
+                    opt_type: $opt_type,
+                }
+            );
+        };
+    }
+
+    fn spin(default: isize, min: isize, max: isize) -> OptionType {
+        OptionType::Spin { default, min, max }
+    }
+
+    fn check(default: bool) -> OptionType {
+        OptionType::Check { default }
+    }
+
+    fn string(default: &str) -> OptionType {
+        OptionType::String {
+            default: default.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_parse_option_line() -> Result<()> {
+        test_option_line!(
+            "option name Debug Log File type string default",
+            "Debug Log File",
+            string("")
+        );
+        test_option_line!(
+            "option name Threads type spin default 1 min 1 max 512",
+            "Threads",
+            spin(1, 1, 512)
+        );
+        test_option_line!(
+            "option name Hash type spin default 16 min 1 max 2048",
+            "Hash",
+            spin(16, 1, 2048)
+        );
+        test_option_line!(
+            "option name Clear Hash type button",
+            "Clear Hash",
+            OptionType::Button
+        );
+        test_option_line!(
+            "option name Ponder type check default false",
+            "Ponder",
+            check(false)
+        );
+        test_option_line!(
+            "option name MultiPV type spin default 1 min 1 max 500",
+            "MultiPV",
+            spin(1, 1, 500)
+        );
+        test_option_line!(
+            "option name Skill Level type spin default 20 min 0 max 20",
+            "Skill Level",
+            spin(20, 0, 20)
+        );
+        test_option_line!(
+            "option name Move Overhead type spin default 10 min 0 max 5000",
+            "Move Overhead",
+            spin(10, 0, 5000)
+        );
+        test_option_line!(
+            "option name Slow Mover type spin default 100 min 10 max 1000",
+            "Slow Mover",
+            spin(100, 10, 1000)
+        );
+        test_option_line!(
+            "option name nodestime type spin default 0 min 0 max 10000",
+            "nodestime",
+            spin(0, 0, 10000)
+        );
+        test_option_line!(
+            "option name UCI_Chess960 type check default false",
+            "UCI_Chess960",
+            check(false)
+        );
+        test_option_line!(
+            "option name UCI_AnalyseMode type check default false",
+            "UCI_AnalyseMode",
+            check(false)
+        );
+        test_option_line!(
+            "option name UCI_LimitStrength type check default false",
+            "UCI_LimitStrength",
+            check(false)
+        );
+        test_option_line!(
+            "option name UCI_Elo type spin default 1350 min 1350 max 2850",
+            "UCI_Elo",
+            spin(1350, 1350, 2850)
+        );
+        test_option_line!(
+            "option name UCI_ShowWDL type check default false",
+            "UCI_ShowWDL",
+            check(false)
+        );
+        test_option_line!(
+            "option name SyzygyPath type string default <empty>",
+            "SyzygyPath",
+            string("")
+        );
+        test_option_line!(
+            "option name SyzygyProbeDepth type spin default 1 min 1 max 100",
+            "SyzygyProbeDepth",
+            spin(1, 1, 100)
+        );
+        test_option_line!(
+            "option name Syzygy50MoveRule type check default true",
+            "Syzygy50MoveRule",
+            check(true)
+        );
+        test_option_line!(
+            "option name SyzygyProbeLimit type spin default 7 min 0 max 7",
+            "SyzygyProbeLimit",
+            spin(7, 0, 7)
+        );
+        test_option_line!(
+            "option name Use NNUE type check default true",
+            "Use NNUE",
+            check(true)
+        );
+        test_option_line!(
+            "option name EvalFile type string default nn-6877cd24400e.nnue",
+            "EvalFile",
+            string("nn-6877cd24400e.nnue")
+        );
+        test_option_line!(
+            "option name Style type combo default Very Solid var Very Solid var Normal var Risky Play",
+            "Style",
+            OptionType::Combo {
+                default: "Very Solid".to_string(),
+                options: vec![
+                    "Very Solid".to_string(),
+                    "Normal".to_string(),
+                    "Risky Play".to_string(),
+                ],
+            }
+        );
+        assert_eq!(
+            parse_uci("option name Hash type spin default 16".to_string()),
+            Err(UCIError::ParseError {
+                line: "option name Hash type spin default 16".to_string(),
+                position: 37,
+            })
+        );
+        assert_eq!(
+            parse_uci("option name Hash type spin default x min 1 max 2".to_string()),
+            Err(UCIError::ParseError {
+                line: "option name Hash type spin default x min 1 max 2".to_string(),
+                position: 35,
+            })
+        );
+        assert_eq!(
+            parse_uci("option name Hash type wheel".to_string()),
+            Err(UCIError::ParseError {
+                line: "option name Hash type wheel".to_string(),
+                position: 22,
+            })
+        );
+        assert!(parse_uci("option type button".to_string()).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_parse_id_line() -> Result<()> {
+        assert_eq!(
+            parse_uci("id name Stockfish 15".to_string())?,
+            UCI::Id {
+                name: Some("Stockfish 15".to_string()),
+                author: None,
+            }
+        );
+        assert_eq!(
+            parse_uci("id   author the Stockfish developers (see AUTHORS file)".to_string())?,
+            UCI::Id {
+                name: None,
+                author: Some("the Stockfish developers (see AUTHORS file)".to_string()),
+            }
+        );
+        assert_eq!(
+            parse_uci("  id version 15".to_string()),
+            Err(UCIError::ParseError {
+                line: "id version 15".to_string(),
+                position: 3,
+            })
+        );
+        assert!(parse_uci("id name".to_string()).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_parse_check_status() -> Result<()> {
+        assert_eq!(
+            parse_uci("copyprotection checking".to_string())?,
+            UCI::CopyProtection(CheckStatus::Checking)
+        );
+        assert_eq!(
+            parse_uci("copyprotection error".to_string())?,
+            UCI::CopyProtection(CheckStatus::Error)
+        );
+        assert_eq!(
+            parse_uci("registration ok".to_string())?,
+            UCI::Registration(CheckStatus::Ok)
+        );
+        assert_eq!(
+            parse_uci("registration pending".to_string()),
+            Err(UCIError::ParseError {
+                line: "registration pending".to_string(),
+                position: 13,
+            })
+        );
+        assert!(parse_uci("copyprotection".to_string()).is_err());
         Ok(())
     }
 }
@@ -0,0 +1,142 @@
+use crate::board::{Board, FenError};
+use crate::moves::MoveError;
+use std::fmt::Display;
+use thiserror::Error;
+
+/// Errors produced checking a position
+#[derive(Error, Debug, PartialEq)]
+pub enum PositionError {
+    /// The starting position isn't a valid FEN
+    #[error(transparent)]
+    Fen(#[from] FenError),
+
+    /// A move can't be played from the starting position
+    #[error(transparent)]
+    Move(#[from] MoveError),
+}
+
+/// Position to search, described as a starting position plus the moves played from it.
+///
+/// ```
+/// use async_uci::position::Position;
+///
+/// let pos = Position::startpos().moves(["e2e4", "e7e5"]);
+/// assert_eq!(pos.to_string(), "position startpos moves e2e4 e7e5");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Position {
+    /// FEN of the starting position, `None` for the standard starting position
+    pub fen: Option<String>,
+    /// Moves played from the starting position in long algebraic notation
+    pub moves: Vec<String>,
+}
+
+impl Position {
+    /// Standard chess starting position
+    pub fn startpos() -> Self {
+        Self::default()
+    }
+
+    /// Position described by a FEN string
+    pub fn fen(fen: impl Into<String>) -> Self {
+        Position {
+            fen: Some(fen.into()),
+            moves: Vec::new(),
+        }
+    }
+
+    /// Position starting from a board
+    pub fn from_board(board: &Board) -> Self {
+        Self::fen(board.to_string())
+    }
+
+    /// Parse and validate the starting position and play the moves from it, returning the
+    /// board the engine will search
+    pub fn board(&self) -> Result<Board, PositionError> {
+        let board = match &self.fen {
+            Some(fen) => fen.parse()?,
+            None => Board::startpos(),
+        };
+        Ok(board.play_moves(&self.moves)?)
+    }
+
+    /// Append a list of moves to the position
+    pub fn moves<I, S>(mut self, moves: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.moves.extend(moves.into_iter().map(Into::into));
+        self
+    }
+
+    /// Append a single move to the position
+    pub fn push(&mut self, mv: impl Into<String>) {
+        self.moves.push(mv.into());
+    }
+}
+
+impl Display for Position {
+    /// Format as a `position` command, without the trailing newline
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.fen {
+            Some(fen) => f.write_fmt(format_args!("position fen {}", fen))?,
+            None => f.write_str("position startpos")?,
+        }
+        if !self.moves.is_empty() {
+            f.write_fmt(format_args!(" moves {}", self.moves.join(" ")))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::board::{Board, FenError};
+    use crate::moves::MoveError;
+    use crate::position::{Position, PositionError};
+
+    #[test]
+    fn test_position() {
+        assert_eq!(Position::startpos().to_string(), "position startpos");
+        let fen = "r2qk2r/pp3ppp/B1nbpn2/2pp1b2/Q2P1B2/2P1PN2/PP1N1PPP/R3K2R b KQkq - 4 8";
+        assert_eq!(
+            Position::fen(fen).to_string(),
+            format!("position fen {}", fen)
+        );
+        let mut pos = Position::fen(fen).moves(["d8a5"]);
+        pos.push("a4a5");
+        assert_eq!(
+            pos.to_string(),
+            format!("position fen {} moves d8a5 a4a5", fen)
+        );
+    }
+
+    #[test]
+    fn test_position_board() {
+        assert_eq!(Position::startpos().board(), Ok(Board::startpos()));
+        let fen = "8/8/4k3/8/8/3K4/8/8 b - - 40 90";
+        let board: Board = fen.parse().unwrap();
+        assert_eq!(Position::from_board(&board), Position::fen(fen));
+        assert_eq!(Position::from_board(&board).board(), Ok(board));
+        assert_eq!(
+            Position::fen("8/8/8/8/8/8/8/8 w - - 0 1").board(),
+            Err(PositionError::Fen(FenError::Illegal(
+                "White has 0 kings".to_string()
+            )))
+        );
+        assert_eq!(
+            Position::startpos().moves(["e2e4", "e7e5", "g1f3"]).board(),
+            Board::startpos()
+                .play_moves(["e2e4", "e7e5", "g1f3"])
+                .map_err(PositionError::from)
+        );
+        assert_eq!(
+            Position::startpos().moves(["e2e4", "e2e4"]).board(),
+            Err(PositionError::Move(MoveError::Illegal {
+                mv: "e2e4".to_string(),
+                fen: "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1".to_string()
+            }))
+        );
+    }
+}
@@ -0,0 +1,146 @@
+use crate::board::{Board, Color, PieceKind};
+use crate::moves::{Move, MoveError};
+
+impl Board {
+    /// Standard Algebraic Notation of a move, i.e. `Nbd2`, `exf6`, `a8=Q+` or `O-O`, failing
+    /// if the move isn't legal
+    pub fn san(&self, mv: &Move) -> Result<String, MoveError> {
+        if !self.is_legal(mv) {
+            return Err(MoveError::Illegal {
+                mv: mv.to_string(),
+                fen: self.to_string(),
+            });
+        }
+        Ok(self.legal_san(mv))
+    }
+
+    /// SAN of a move already known to be legal
+    fn legal_san(&self, mv: &Move) -> String {
+        let piece = self.piece_at(mv.from).expect("no piece to move");
+        let mut san = String::new();
+        let castling = piece.kind == PieceKind::King && mv.from.file().abs_diff(mv.to.file()) == 2;
+        if castling {
+            san.push_str(match mv.to.file() {
+                6 => "O-O",
+                _ => "O-O-O",
+            });
+        } else {
+            let capture = self.piece_at(mv.to).is_some()
+                || (piece.kind == PieceKind::Pawn && mv.from.file() != mv.to.file());
+            match piece.kind {
+                PieceKind::Pawn if capture => san.push((b'a' + mv.from.file()) as char),
+                PieceKind::Pawn => {}
+                kind => {
+                    san.push(kind.to_char().to_ascii_uppercase());
+                    san.push_str(&self.disambiguation(mv, kind));
+                }
+            }
+            if capture {
+                san.push('x');
+            }
+            san.push_str(&mv.to.to_string());
+            if let Some(kind) = mv.promotion {
+                san.push('=');
+                san.push(kind.to_char().to_ascii_uppercase());
+            }
+        }
+        let after = self.apply(mv);
+        if after.is_checkmate() {
+            san.push('#');
+        } else if after.is_check() {
+            san.push('+');
+        }
+        san
+    }
+
+    /// Moves in long algebraic notation converted to SAN with move numbers, i.e.
+    /// `8...Qa5 9.Qxa5 Nxa5`, failing on the first illegal move
+    pub fn san_line<I, S>(&self, moves: I) -> Result<String, MoveError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut board = self.clone();
+        let mut line = Vec::new();
+        for mv in moves {
+            let mv = board.parse_move(mv.as_ref())?;
+            let san = board.legal_san(&mv);
+            line.push(match (board.side_to_move, line.is_empty()) {
+                (Color::White, _) => format!("{}.{}", board.fullmove_number, san),
+                (Color::Black, true) => format!("{}...{}", board.fullmove_number, san),
+                (Color::Black, false) => san,
+            });
+            board = board.apply(&mv);
+        }
+        Ok(line.join(" "))
+    }
+
+    /// Origin file, rank or square needed to tell a move apart from other pieces of the same
+    /// kind reaching the same square
+    fn disambiguation(&self, mv: &Move, kind: PieceKind) -> String {
+        let others: Vec<Move> = self
+            .legal_moves()
+            .into_iter()
+            .filter(|other| other.to == mv.to && other.from != mv.from)
+            .filter(|other| self.piece_at(other.from).is_some_and(|p| p.kind == kind))
+            .collect();
+        let file = (b'a' + mv.from.file()) as char;
+        let rank = (b'1' + mv.from.rank()) as char;
+        if others.is_empty() {
+            String::new()
+        } else if others.iter().all(|o| o.from.file() != mv.from.file()) {
+            file.to_string()
+        } else if others.iter().all(|o| o.from.rank() != mv.from.rank()) {
+            rank.to_string()
+        } else {
+            mv.from.to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::board::Board;
+    use crate::moves::{Move, MoveError};
+
+    #[test]
+    fn test_san() {
+        let san = |fen: &str, moves: &[&str]| fen.parse::<Board>().unwrap().san_line(moves);
+        let fen = "r2qk2r/pp3ppp/B1nbpn2/2pp1b2/Q2P1B2/2P1PN2/PP1N1PPP/R3K2R b KQkq - 4 8";
+        assert_eq!(
+            san(fen, &["d8a5", "a4a5", "c6a5", "e1g1", "e8c8"]),
+            Ok("8...Qa5 9.Qxa5 Nxa5 10.O-O O-O-O".to_string())
+        );
+        assert_eq!(
+            Board::startpos().san_line(["f2f3", "e7e5", "g2g4", "d8h4"]),
+            Ok("1.f3 e5 2.g4 Qh4#".to_string())
+        );
+        let fen = "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3";
+        assert_eq!(san(fen, &["e5f6"]), Ok("3.exf6".to_string()));
+        assert_eq!(
+            san("4k3/P7/8/8/8/8/8/4K3 w - - 0 1", &["a7a8q"]),
+            Ok("1.a8=Q+".to_string())
+        );
+
+        let knights = "4k3/8/8/8/8/1N3N2/8/1N2K3 w - - 0 1";
+        assert_eq!(san(knights, &["f3d2"]), Ok("1.Nfd2".to_string()));
+        assert_eq!(san(knights, &["b1d2"]), Ok("1.N1d2".to_string()));
+        let queens = "4k3/8/8/8/8/Q7/8/Q1Q1K3 w - - 0 1";
+        assert_eq!(san(queens, &["a1b2"]), Ok("1.Qa1b2".to_string()));
+        assert_eq!(san(queens, &["a3a2"]), Ok("1.Q3a2".to_string()));
+
+        assert!(matches!(
+            Board::startpos().san_line(["e2e4", "e2e4"]),
+            Err(MoveError::Illegal { .. })
+        ));
+        let empty: Move = "e4e5".parse().unwrap();
+        assert!(matches!(
+            Board::startpos().san(&empty),
+            Err(MoveError::Illegal { .. })
+        ));
+        assert_eq!(
+            Board::startpos().san(&"g1f3".parse().unwrap()),
+            Ok("Nf3".to_string())
+        );
+    }
+}
@@ -0,0 +1,152 @@
+use crate::board::Color;
+use std::{cmp::Ordering, fmt::Display, ops::Neg};
+
+/// Score of a position as reported by the engine, from the point of view of the side to move
+/// unless converted with `perspective` or `white`.
+///
+/// Scores are ordered from worst to best, any mate for the side is better than any centipawn
+/// advantage and a shorter mate is better than a longer one.
+///
+/// ```
+/// use async_uci::board::Color;
+/// use async_uci::score::Score;
+///
+/// assert_eq!(Score::Centipawns(123).to_string(), "+1.23");
+/// assert_eq!(Score::Mate(-4).to_string(), "#-4");
+/// assert!(Score::Mate(2) > Score::Mate(5));
+/// assert!(Score::Mate(-5) > Score::Mate(-2));
+/// assert_eq!(Score::Centipawns(40).white(Color::Black), Score::Centipawns(-40));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Score {
+    /// Advantage in hundredths of a pawn
+    Centipawns(i32),
+    /// Mate in a number of moves, negative when the side is getting mated
+    Mate(i32),
+}
+
+impl Score {
+    /// Score from the point of view of `color`, for a score reported with `side_to_move` to
+    /// play
+    pub fn perspective(self, side_to_move: Color, color: Color) -> Self {
+        if side_to_move == color {
+            self
+        } else {
+            -self
+        }
+    }
+
+    /// Score from the point of view of white, for a score reported with `side_to_move` to play
+    pub fn white(self, side_to_move: Color) -> Self {
+        self.perspective(side_to_move, Color::White)
+    }
+
+    /// Check if the score is a forced mate, for either side
+    pub fn is_mate(&self) -> bool {
+        matches!(self, Score::Mate(_))
+    }
+
+    /// Single value ordering every score, mates are placed beyond any centipawn value
+    fn rank(&self) -> i64 {
+        const MATE: i64 = 1 << 40;
+        match *self {
+            Score::Centipawns(cp) => cp as i64,
+            Score::Mate(moves) if moves > 0 => MATE - moves as i64,
+            // mate 0 means the side is already mated, the worst possible score
+            Score::Mate(moves) => -MATE - moves as i64,
+        }
+    }
+}
+
+impl Neg for Score {
+    type Output = Score;
+
+    /// Score from the point of view of the other side
+    fn neg(self) -> Self::Output {
+        match self {
+            Score::Centipawns(cp) => Score::Centipawns(-cp),
+            Score::Mate(moves) => Score::Mate(-moves),
+        }
+    }
+}
+
+impl Ord for Score {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.rank().cmp(&other.rank())
+    }
+}
+
+impl PartialOrd for Score {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Display for Score {
+    /// Format centipawns as signed pawns (`+1.23`) and mates as `#<moves>` (`#-4`)
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Score::Centipawns(cp) => {
+                let sign = if *cp < 0 { "-" } else { "+" };
+                let cp = cp.unsigned_abs();
+                f.write_fmt(format_args!("{}{}.{:02}", sign, cp / 100, cp % 100))
+            }
+            Score::Mate(moves) => f.write_fmt(format_args!("#{}", moves)),
+        }
+    }
+}
+
+/// Whether a score is exact or only a bound of the real score, as engines report while
+/// searching with aspiration windows
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum Bound {
+    #[default]
+    Exact,
+    /// The real score is at least the reported one
+    Lower,
+    /// The real score is at most the reported one
+    Upper,
+}
+
+#[cfg(test)]
+mod test {
+    use crate::board::Color;
+    use crate::score::Score;
+
+    #[test]
+    fn test_score() {
+        let mut scores = vec![
+            Score::Centipawns(-30),
+            Score::Mate(-1),
+            Score::Mate(3),
+            Score::Centipawns(250),
+            Score::Mate(0),
+            Score::Mate(1),
+            Score::Mate(-6),
+        ];
+        scores.sort();
+        assert_eq!(
+            scores,
+            vec![
+                Score::Mate(0),
+                Score::Mate(-1),
+                Score::Mate(-6),
+                Score::Centipawns(-30),
+                Score::Centipawns(250),
+                Score::Mate(3),
+                Score::Mate(1),
+            ]
+        );
+        assert_eq!(Score::Centipawns(0).to_string(), "+0.00");
+        assert_eq!(Score::Centipawns(-5).to_string(), "-0.05");
+        assert_eq!(Score::Centipawns(-1234).to_string(), "-12.34");
+        assert_eq!(Score::Mate(3).to_string(), "#3");
+        let score = Score::Mate(3);
+        assert_eq!(
+            score.perspective(Color::White, Color::Black),
+            Score::Mate(-3)
+        );
+        assert_eq!(score.perspective(Color::Black, Color::Black), score);
+        assert_eq!(score.white(Color::White), score);
+    }
+}
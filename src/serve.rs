@@ -0,0 +1,329 @@
+use crate::cli::ServeArgs;
+use anyhow::{bail, Result};
+use async_uci::builder::EngineBuilder;
+use async_uci::engine::{self, ChessEngine, Engine};
+use async_uci::parse::OptionType;
+use std::sync::Arc;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+    net::TcpListener,
+    sync::{Mutex, Semaphore},
+    time::Duration,
+};
+use tokio_stream::StreamExt;
+
+/// Time to wait before accepting connections again after failing to accept one
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Engines shared between connections, at most one connection uses each engine at a time
+struct Pool {
+    engines: Mutex<Vec<Engine>>,
+    available: Semaphore,
+}
+
+/// Server spawning or lending an engine to every connection
+struct Server {
+    builder: EngineBuilder,
+    pool: Option<Pool>,
+}
+
+impl Server {
+    /// Relay a connection to a new engine, or to one from the pool if there is one
+    async fn handle<S>(&self, stream: S) -> Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        let pool = match &self.pool {
+            Some(pool) => pool,
+            None => {
+                let mut eng = self.builder.spawn().await?;
+                let res = relay(stream, &mut eng).await;
+                eng.shutdown().await?;
+                return res;
+            }
+        };
+        let permit = pool.available.acquire().await?;
+        let mut eng = pool
+            .engines
+            .lock()
+            .await
+            .pop()
+            .expect("permit without engine");
+        let res = relay(stream, &mut eng).await;
+        // leave the engine as new for the next connection, replacing it if it died
+        if reset(&mut eng).await.is_err() {
+            eng = match self.builder.spawn().await {
+                Ok(eng) => eng,
+                Err(err) => {
+                    // the pool is left with one engine less, and as many permits
+                    permit.forget();
+                    return Err(err.into());
+                }
+            };
+        }
+        pool.engines.lock().await.push(eng);
+        res
+    }
+}
+
+/// Stop any search and set every option back to the default advertised by the engine, so that
+/// nothing set by a client carries over to the next one
+async fn reset(eng: &mut Engine) -> engine::Result<()> {
+    eng.send_raw("stop").await?;
+    for opt in eng.get_options().await? {
+        let default = match opt.opt_type {
+            OptionType::Check { default } => default.to_string(),
+            OptionType::Spin { default, .. } => default.to_string(),
+            OptionType::Combo { default, .. } => default,
+            OptionType::String { default } => default,
+            OptionType::Button => continue,
+        };
+        eng.set_option(opt.name, default).await?;
+    }
+    eng.new_game().await
+}
+
+/// Relay lines between a client and an engine until either of them disconnects or the client
+/// sends `quit`. The connection is closed with an error if the engine exits.
+async fn relay<S>(stream: S, eng: &mut Engine) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut commands = BufReader::new(reader).lines();
+    let mut output = eng.subscribe_raw();
+    let exited = eng.exited();
+    tokio::pin!(exited);
+    loop {
+        tokio::select! {
+            // the engine output is relayed before noticing it exited
+            biased;
+            command = commands.next_line() => match command? {
+                Some(command) if command.trim() == "quit" => break,
+                Some(command) => eng.send_raw(&command).await?,
+                None => break,
+            },
+            line = output.next() => match line {
+                Some(Ok(line)) => {
+                    writer.write_all(line.as_bytes()).await?;
+                    writer.write_all(b"\n").await?;
+                    writer.flush().await?;
+                }
+                // lagging only drops engine output, keep relaying
+                Some(Err(_)) => continue,
+                None => break,
+            },
+            _ = &mut exited => bail!("engine exited"),
+        }
+    }
+    Ok(())
+}
+
+/// Listen for connections and relay each of them to an engine
+pub async fn serve(builder: EngineBuilder, args: ServeArgs) -> Result<()> {
+    let pool = match args.pool {
+        0 => None,
+        size => {
+            let mut engines = Vec::with_capacity(size);
+            for _ in 0..size {
+                engines.push(builder.spawn().await?);
+            }
+            Some(Pool {
+                engines: Mutex::new(engines),
+                available: Semaphore::new(size),
+            })
+        }
+    };
+    let server = Arc::new(Server { builder, pool });
+    if let Some(addr) = args.tcp {
+        let listener = TcpListener::bind(&addr).await?;
+        println!("Listening on tcp://{}", listener.local_addr()?);
+        loop {
+            let (stream, peer) = match listener.accept().await {
+                Ok(conn) => conn,
+                Err(err) => {
+                    accept_failed(err).await;
+                    continue;
+                }
+            };
+            let server = server.clone();
+            tokio::spawn(async move {
+                if let Err(err) = server.handle(stream).await {
+                    eprintln!("Connection from {peer} failed: {err:#}");
+                }
+            });
+        }
+    }
+    if let Some(path) = args.unix {
+        return serve_unix(server, path).await;
+    }
+    bail!("either --tcp or --unix must be given")
+}
+
+/// Report a connection that couldn't be accepted and back off. Failures such as running out of
+/// file descriptors are usually transient, so the server keeps running.
+async fn accept_failed(err: std::io::Error) {
+    eprintln!("Couldn't accept connection: {err}");
+    tokio::time::sleep(ACCEPT_BACKOFF).await;
+}
+
+#[cfg(unix)]
+async fn serve_unix(server: Arc<Server>, path: String) -> Result<()> {
+    let listener = tokio::net::UnixListener::bind(&path)?;
+    println!("Listening on unix://{path}");
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(err) => {
+                accept_failed(err).await;
+                continue;
+            }
+        };
+        let server = server.clone();
+        tokio::spawn(async move {
+            if let Err(err) = server.handle(stream).await {
+                eprintln!("Connection failed: {err:#}");
+            }
+        });
+    }
+}
+
+#[cfg(not(unix))]
+async fn serve_unix(_server: Arc<Server>, _path: String) -> Result<()> {
+    bail!("unix sockets are not supported on this platform")
+}
+
+#[cfg(test)]
+mod test {
+    use crate::serve::{relay, Pool, Server};
+    use anyhow::Result;
+    use async_uci::builder::EngineBuilder;
+    use async_uci::engine::{ChessEngine, Engine};
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::sync::{Mutex, Semaphore};
+    use tokio::time::Duration;
+
+    #[tokio::test]
+    async fn test_relay() -> Result<()> {
+        let (client, server) = tokio::io::duplex(4096);
+        let relayed = tokio::spawn(async move {
+            let mut eng = Engine::new(test_file!("fakefish.sh")).await?;
+            relay(server, &mut eng).await?;
+            eng.shutdown().await?;
+            anyhow::Ok(())
+        });
+        let (reader, mut writer) = tokio::io::split(client);
+        let mut lines = BufReader::new(reader).lines();
+        writer.write_all(b"uci\n").await?;
+        while let Some(line) = lines.next_line().await? {
+            if line == "uciok" {
+                break;
+            }
+        }
+        writer.write_all(b"isready\ngo depth 2\n").await?;
+        let mut last = String::new();
+        while let Some(line) = lines.next_line().await? {
+            if line.starts_with("bestmove") {
+                last = line;
+                break;
+            }
+        }
+        assert_eq!(last, "bestmove a8b8 ponder f4d6");
+        writer.write_all(b"quit\n").await?;
+        relayed.await??;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_relay_crash() -> Result<()> {
+        let marker = std::env::temp_dir().join(format!("crashfish-{}-relay", std::process::id()));
+        let _ = std::fs::remove_file(&marker);
+        let mut eng = EngineBuilder::new(test_file!("crashfish.sh"))
+            .env("CRASHFISH_MARKER", &marker)
+            .spawn()
+            .await?;
+        let (client, server) = tokio::io::duplex(4096);
+        let relayed = tokio::spawn(async move { relay(server, &mut eng).await });
+        let (reader, mut writer) = tokio::io::split(client);
+        let mut lines = BufReader::new(reader).lines();
+        writer.write_all(b"uci\ngo depth 1\n").await?;
+        // the connection is closed once the engine crashes on go
+        let received = async {
+            let mut received = Vec::new();
+            while let Some(line) = lines.next_line().await? {
+                received.push(line);
+            }
+            anyhow::Ok(received)
+        };
+        let received = tokio::time::timeout(Duration::from_secs(5), received).await??;
+        assert_eq!(received.last().map(String::as_str), Some("uciok"));
+        assert!(relayed.await?.is_err());
+        std::fs::remove_file(&marker)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pool_reuse() -> Result<()> {
+        let mut eng = Engine::new(test_file!("fakefish.sh")).await?;
+        eng.start_uci().await?;
+        let server = Server {
+            builder: EngineBuilder::new(test_file!("fakefish.sh")),
+            pool: Some(Pool {
+                engines: Mutex::new(vec![eng]),
+                available: Semaphore::new(1),
+            }),
+        };
+        // the option set by the first client mustn't change the search of the second one
+        let commands = ["setoption name MultiPV value 2\nisready\n", "go depth 2\n"];
+        for (ix, command) in commands.into_iter().enumerate() {
+            let (client, stream) = tokio::io::duplex(4096);
+            let (reader, mut writer) = tokio::io::split(client);
+            let mut lines = BufReader::new(reader).lines();
+            let handled = server.handle(stream);
+            let client = async move {
+                writer.write_all(command.as_bytes()).await?;
+                let mut received = Vec::new();
+                while let Some(line) = lines.next_line().await? {
+                    let done = line == "readyok" || line.starts_with("bestmove");
+                    received.push(line);
+                    if done {
+                        break;
+                    }
+                }
+                writer.write_all(b"quit\n").await?;
+                anyhow::Ok(received)
+            };
+            let (handled, received) = tokio::join!(handled, client);
+            handled?;
+            let received = received?;
+            match ix {
+                0 => assert_eq!(received, vec!["readyok".to_string()]),
+                _ => {
+                    // info string, two info lines and the best move
+                    assert_eq!(received.len(), 4);
+                    assert!(received.iter().all(|line| !line.contains("multipv 2")));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pool_respawn_failure() -> Result<()> {
+        // the engine exits right away, and can't be replaced
+        let server = Server {
+            builder: EngineBuilder::new(test_file!("nofish.sh")),
+            pool: Some(Pool {
+                engines: Mutex::new(vec![Engine::new("true").await?]),
+                available: Semaphore::new(1),
+            }),
+        };
+        let (client, stream) = tokio::io::duplex(4096);
+        drop(client);
+        assert!(server.handle(stream).await.is_err());
+        let pool = server.pool.as_ref().expect("no pool");
+        assert_eq!(pool.available.available_permits(), 0);
+        assert!(pool.engines.lock().await.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,477 @@
+use crate::builder::EngineBuilder;
+use crate::engine::{BestMove, ChessEngine, Engine, EngineError, EngineOption, Evaluation, Result};
+use crate::go::GoParams;
+use crate::position::Position;
+use async_trait::async_trait;
+use std::process::ExitStatus;
+
+/// Default amount of times the engine is respawned before giving up
+const DEFAULT_MAX_RESTARTS: usize = 3;
+
+/// Supervisor wraps an `Engine` and respawns it whenever it crashes. After respawning, the UCI
+/// handshake, the options set so far and the current position are replayed, and the failed
+/// command is retried. A search interrupted by a crash is started again, unless it was stopped.
+pub struct Supervisor {
+    builder: EngineBuilder,
+    engine: Engine,
+    started: bool,
+    debug: bool,
+    registration: Option<Registration>,
+    options: Vec<(String, String)>,
+    position: Option<Position>,
+    search: Option<GoParams>,
+    pondering: bool,
+    /// The search was stopped, its result is kept for `best_move` but it isn't replayed
+    stopped: bool,
+    /// Total amount of respawns, for monitoring
+    restarts: usize,
+    /// Respawns since an engine last completed a command
+    failures: usize,
+    max_restarts: usize,
+    /// The engine was asked to quit, so it's not respawned anymore
+    shut_down: bool,
+}
+
+/// Registration answered to the engine, replayed after a crash
+#[derive(Clone)]
+enum Registration {
+    Later,
+    Registered { name: String, code: String },
+}
+
+/// Run a call on the supervised engine, respawning the engine and retrying if it crashed
+macro_rules! supervised {
+    ($self:ident, $call:ident($($arg:expr),*)) => {{
+        $self.check_running()?;
+        loop {
+            match $self.engine.$call($($arg),*).await {
+                Err(err) if is_crash(&err) => $self.respawn(err).await?,
+                res => {
+                    if res.is_ok() {
+                        $self.failures = 0;
+                    }
+                    break res;
+                }
+            }
+        }
+    }};
+}
+
+impl Supervisor {
+    pub async fn new(exe_path: &str) -> Result<Self> {
+        Self::from_builder(EngineBuilder::new(exe_path)).await
+    }
+
+    /// Supervise an engine spawned from a builder, which is reused every time the engine is
+    /// respawned
+    pub async fn from_builder(builder: EngineBuilder) -> Result<Self> {
+        Ok(Supervisor {
+            engine: builder.spawn().await?,
+            builder,
+            started: false,
+            debug: false,
+            registration: None,
+            options: Vec::new(),
+            position: None,
+            search: None,
+            pondering: false,
+            stopped: false,
+            restarts: 0,
+            failures: 0,
+            max_restarts: DEFAULT_MAX_RESTARTS,
+            shut_down: false,
+        })
+    }
+
+    /// Set how many times in a row the engine may be respawned before crashes are returned as
+    /// errors, the last crash is returned once they run out. The count starts over once a
+    /// respawned engine completes a command.
+    pub fn set_max_restarts(&mut self, max_restarts: usize) {
+        self.max_restarts = max_restarts;
+    }
+
+    /// Total amount of times the engine has been respawned
+    pub fn restarts(&self) -> usize {
+        self.restarts
+    }
+
+    /// Currently running engine
+    pub fn engine(&self) -> &Engine {
+        &self.engine
+    }
+
+    /// Ask the engine to quit and wait for it to exit, see `Engine::shutdown`. The engine isn't
+    /// respawned afterwards, every command fails with `UnexpectedState` instead.
+    pub async fn shutdown(&mut self) -> Result<Option<ExitStatus>> {
+        self.shut_down = true;
+        self.engine.shutdown().await
+    }
+
+    /// Check the engine wasn't shut down
+    fn check_running(&self) -> Result<()> {
+        if self.shut_down {
+            return Err(EngineError::UnexpectedState(
+                "engine was shut down".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Replace the crashed engine with a new process and restore its previous state, trying
+    /// again if the new engine crashes while it's being restored
+    async fn respawn(&mut self, mut err: EngineError) -> Result<()> {
+        loop {
+            if self.failures >= self.max_restarts {
+                return Err(err);
+            }
+            self.failures += 1;
+            self.restarts += 1;
+            match self.restore().await {
+                Err(crash) if is_crash(&crash) => err = crash,
+                res => return res,
+            }
+        }
+    }
+
+    /// Spawn a new engine and replay the handshake, options, position and search
+    async fn restore(&mut self) -> Result<()> {
+        self.engine = self.builder.spawn().await?;
+        if self.started {
+            self.engine.start_uci().await?;
+        }
+        match self.registration.clone() {
+            Some(Registration::Later) => self.engine.register_later().await?,
+            Some(Registration::Registered { name, code }) => {
+                self.engine.register(&name, &code).await?
+            }
+            None => {}
+        }
+        if self.debug {
+            self.engine.set_debug(true).await?;
+        }
+        for (name, value) in self.options.iter() {
+            self.engine.set_option(name.clone(), value.clone()).await?;
+        }
+        if let Some(position) = &self.position {
+            self.engine.set_position(position).await?;
+        }
+        if let Some(params) = self.search.as_ref().filter(|_| !self.stopped) {
+            if self.pondering {
+                self.engine.go(params.clone().ponder()).await?;
+            } else {
+                self.engine.go(params.clone()).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Remember an option value to replay it after a crash
+    fn record_option(&mut self, name: &str, value: String) {
+        self.options
+            .retain(|(opt, _)| !opt.eq_ignore_ascii_case(name));
+        self.options.push((name.to_string(), value));
+    }
+}
+
+/// Check if an error was caused by the engine crashing
+fn is_crash(err: &EngineError) -> bool {
+    matches!(err, EngineError::Crashed { .. })
+}
+
+#[async_trait]
+impl ChessEngine for Supervisor {
+    async fn start_uci(&mut self) -> Result<()> {
+        supervised!(self, start_uci())?;
+        self.started = true;
+        Ok(())
+    }
+
+    async fn new_game(&mut self) -> Result<()> {
+        supervised!(self, new_game())
+    }
+
+    async fn register(&mut self, name: &str, code: &str) -> Result<()> {
+        supervised!(self, register(name, code))?;
+        self.registration = Some(Registration::Registered {
+            name: name.to_string(),
+            code: code.to_string(),
+        });
+        Ok(())
+    }
+
+    async fn register_later(&mut self) -> Result<()> {
+        supervised!(self, register_later())?;
+        self.registration = Some(Registration::Later);
+        Ok(())
+    }
+
+    async fn set_debug(&mut self, on: bool) -> Result<()> {
+        supervised!(self, set_debug(on))?;
+        self.debug = on;
+        Ok(())
+    }
+
+    async fn set_position(&mut self, position: &Position) -> Result<()> {
+        supervised!(self, set_position(position))?;
+        self.position = Some(position.clone());
+        Ok(())
+    }
+
+    async fn go(&mut self, params: GoParams) -> Result<()> {
+        self.search = None;
+        self.pondering = false;
+        self.stopped = false;
+        supervised!(self, go(params.clone()))?;
+        self.search = Some(params);
+        Ok(())
+    }
+
+    async fn go_infinite(&mut self) -> Result<()> {
+        self.go(GoParams::new().infinite()).await
+    }
+
+    async fn go_depth(&mut self, depth: usize) -> Result<()> {
+        self.go(GoParams::new().depth(depth)).await
+    }
+
+    async fn go_time(&mut self, ms: usize) -> Result<()> {
+        self.go(GoParams::new().movetime(ms)).await
+    }
+
+    async fn go_mate(&mut self, mate_in: usize) -> Result<()> {
+        self.go(GoParams::new().mate(mate_in)).await
+    }
+
+    async fn stop(&mut self) -> Result<()> {
+        supervised!(self, stop())?;
+        self.pondering = false;
+        self.stopped = true;
+        Ok(())
+    }
+
+    async fn ponder(
+        &mut self,
+        position: &Position,
+        ponder_move: &str,
+        params: GoParams,
+    ) -> Result<()> {
+        self.search = None;
+        self.pondering = false;
+        self.stopped = false;
+        supervised!(self, ponder(position, ponder_move, params.clone()))?;
+        let mut position = position.clone();
+        position.push(ponder_move);
+        self.position = Some(position);
+        self.search = Some(params);
+        self.pondering = true;
+        Ok(())
+    }
+
+    async fn ponderhit(&mut self) -> Result<()> {
+        supervised!(self, ponderhit())?;
+        self.pondering = false;
+        Ok(())
+    }
+
+    async fn ponder_miss(&mut self) -> Result<()> {
+        self.check_running()?;
+        let res = self.engine.ponder_miss().await;
+        self.search = None;
+        self.pondering = false;
+        match res {
+            // the ponder search is lost along with the crashed engine, which is all we wanted
+            Err(err) if is_crash(&err) => self.respawn(err).await,
+            res => res,
+        }
+    }
+
+    async fn best_move(&mut self) -> Result<BestMove> {
+        self.check_running()?;
+        if self.search.is_none() {
+            return Err(EngineError::UnexpectedState(
+                "engine is not searching".to_string(),
+            ));
+        }
+        let bm = supervised!(self, best_move());
+        self.search = None;
+        bm
+    }
+
+    async fn get_evaluation(&mut self) -> Option<Evaluation> {
+        self.engine.get_evaluation().await
+    }
+
+    async fn get_lines(&mut self) -> Vec<Evaluation> {
+        self.engine.get_lines().await
+    }
+
+    async fn get_options(&mut self) -> Result<Vec<EngineOption>> {
+        supervised!(self, get_options())
+    }
+
+    async fn set_option(&mut self, option: String, value: String) -> Result<()> {
+        supervised!(self, set_option(option.clone(), value.clone()))?;
+        self.record_option(&option, value);
+        Ok(())
+    }
+
+    async fn set_check(&mut self, option: &str, value: bool) -> Result<()> {
+        supervised!(self, set_check(option, value))?;
+        self.record_option(option, value.to_string());
+        Ok(())
+    }
+
+    async fn set_spin(&mut self, option: &str, value: isize) -> Result<()> {
+        supervised!(self, set_spin(option, value))?;
+        self.record_option(option, value.to_string());
+        Ok(())
+    }
+
+    async fn set_combo(&mut self, option: &str, value: &str) -> Result<()> {
+        supervised!(self, set_combo(option, value))?;
+        self.record_option(option, value.to_string());
+        Ok(())
+    }
+
+    async fn set_string(&mut self, option: &str, value: &str) -> Result<()> {
+        supervised!(self, set_string(option, value))?;
+        self.record_option(option, value.to_string());
+        Ok(())
+    }
+
+    async fn press_button(&mut self, option: &str) -> Result<()> {
+        supervised!(self, press_button(option))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::builder::EngineBuilder;
+    use crate::engine::{ChessEngine, EngineError};
+    use crate::go::GoParams;
+    use crate::position::Position;
+    use crate::supervisor::Supervisor;
+    use std::path::PathBuf;
+
+    /// Supervise crashfish crashing on the given commands, with a marker file for the test
+    async fn crashfish(test: &str, crashes: &str) -> anyhow::Result<(Supervisor, PathBuf)> {
+        let marker =
+            std::env::temp_dir().join(format!("crashfish-{}-{}", std::process::id(), test));
+        let _ = std::fs::remove_file(&marker);
+        let builder = EngineBuilder::new(test_file!("crashfish.sh"))
+            .env("CRASHFISH_MARKER", &marker)
+            .env("CRASHFISH_CRASHES", crashes);
+        Ok((Supervisor::from_builder(builder).await?, marker))
+    }
+
+    #[tokio::test]
+    async fn test_stop() -> anyhow::Result<()> {
+        let mut sup = Supervisor::new(test_file!("fakefish.sh")).await?;
+        sup.start_uci().await?;
+        let fen = "r2qk2r/pp3ppp/B1nbpn2/2pp1b2/Q2P1B2/2P1PN2/PP1N1PPP/R3K2R b KQkq - 4 8";
+        sup.set_position(&Position::fen(fen)).await?;
+        sup.go_infinite().await?;
+        sup.stop().await?;
+        assert_eq!(sup.best_move().await?.bestmove, "a8b8");
+        assert!(matches!(
+            sup.best_move().await,
+            Err(EngineError::UnexpectedState(_))
+        ));
+        assert!(sup.shutdown().await?.unwrap().success());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_crash_and_restart() -> anyhow::Result<()> {
+        let (mut sup, marker) = crashfish("restart", "go").await?;
+        sup.start_uci().await?;
+        sup.set_spin("Hash", 32).await?;
+        sup.go_depth(1).await?;
+        let bm = sup.best_move().await?;
+        assert_eq!(bm.bestmove, "d6f4");
+        assert_eq!(sup.restarts(), 1);
+        std::fs::remove_file(&marker)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_consecutive_crashes() -> anyhow::Result<()> {
+        // a single restart is allowed, but the engine recovers in between the crashes
+        let (mut sup, marker) = crashfish("consecutive", "go ucinewgame").await?;
+        sup.set_max_restarts(1);
+        sup.start_uci().await?;
+        sup.go_depth(1).await?;
+        assert_eq!(sup.best_move().await?.bestmove, "d6f4");
+        sup.new_game().await?;
+        assert_eq!(sup.restarts(), 2);
+        std::fs::remove_file(&marker)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_shutdown() -> anyhow::Result<()> {
+        let (mut sup, marker) = crashfish("shutdown", "").await?;
+        sup.start_uci().await?;
+        assert!(sup.shutdown().await?.unwrap().success());
+        assert!(matches!(
+            sup.go_depth(1).await,
+            Err(EngineError::UnexpectedState(_))
+        ));
+        assert!(matches!(
+            sup.ponder_miss().await,
+            Err(EngineError::UnexpectedState(_))
+        ));
+        assert_eq!(sup.restarts(), 0);
+        std::fs::remove_file(&marker)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_crash_while_restoring() -> anyhow::Result<()> {
+        // the second engine crashes on the handshake replayed after the first crash
+        let (mut sup, marker) = crashfish("restoring", "go uci").await?;
+        sup.start_uci().await?;
+        sup.go_depth(1).await?;
+        assert_eq!(sup.best_move().await?.bestmove, "d6f4");
+        assert_eq!(sup.restarts(), 2);
+        std::fs::remove_file(&marker)?;
+
+        let (mut sup, marker) = crashfish("give-up", "go uci").await?;
+        sup.set_max_restarts(1);
+        sup.start_uci().await?;
+        sup.go_depth(1).await?;
+        assert!(matches!(
+            sup.best_move().await,
+            Err(EngineError::Crashed { .. })
+        ));
+        std::fs::remove_file(&marker)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_crash_while_pondering() -> anyhow::Result<()> {
+        let fen = "r2qk2r/pp3ppp/B1nbpn2/2pp1b2/Q2P1B2/2P1PN2/PP1N1PPP/R3K2R b KQkq - 4 8";
+        let position = Position::fen(fen).moves(["a8b8"]);
+        // the ponder search is replayed, so it can still be confirmed
+        let (mut sup, marker) = crashfish("ponderhit", "go").await?;
+        sup.start_uci().await?;
+        sup.ponder(&position, "b2b3", GoParams::new().depth(1))
+            .await?;
+        // a crash noticed after ponderhit is sent restarts a normal search instead
+        sup.engine().wait_crash().await;
+        sup.ponderhit().await?;
+        assert_eq!(sup.best_move().await?.bestmove, "d6f4");
+        assert_eq!(sup.restarts(), 1);
+        std::fs::remove_file(&marker)?;
+
+        let (mut sup, marker) = crashfish("ponder-miss", "go").await?;
+        sup.start_uci().await?;
+        sup.ponder(&position, "b2b3", GoParams::new().depth(1))
+            .await?;
+        sup.engine().wait_crash().await;
+        sup.ponder_miss().await?;
+        assert_eq!(sup.restarts(), 1);
+        std::fs::remove_file(&marker)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,6 @@
+/// Path of a file in the test resources directory, i.e. `test_file!("fakefish.sh")`
+macro_rules! test_file {
+    ($fname:expr) => {
+        concat!(env!("CARGO_MANIFEST_DIR"), "/res/test/", $fname)
+    };
+}
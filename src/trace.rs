@@ -0,0 +1,107 @@
+use std::{
+    fmt::Display,
+    fs::File,
+    io::{LineWriter, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+/// Direction of a line exchanged with the engine
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Command sent to the engine
+    Sent,
+    /// Line received from the engine's stdout
+    Received,
+}
+
+impl Display for Direction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Direction::Sent => f.write_str(">"),
+            Direction::Received => f.write_str("<"),
+        }
+    }
+}
+
+/// Protocol trace recording every line exchanged with the engine, including the ones that
+/// couldn't be parsed. Each line is written as the seconds elapsed since the trace was created,
+/// the direction and the line itself:
+///
+/// ```text
+///     0.000152 > uci
+///     0.003021 < id name Stockfish 15
+/// ```
+///
+/// Clones write to the same sink, so a single trace can be shared by several engines.
+#[derive(Clone)]
+pub struct Trace {
+    start: Instant,
+    sink: Arc<Mutex<Box<dyn Write + Send>>>,
+}
+
+impl Trace {
+    /// Write the trace to any writer
+    pub fn new(sink: impl Write + Send + 'static) -> Self {
+        Trace {
+            start: Instant::now(),
+            sink: Arc::new(Mutex::new(Box::new(sink))),
+        }
+    }
+
+    /// Write the trace to a file, truncating it if it exists. Lines are flushed as they are
+    /// recorded so the trace is complete even if the program is killed.
+    pub fn to_file(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        Ok(Self::new(LineWriter::new(File::create(path)?)))
+    }
+
+    /// Record a line, errors writing the trace are ignored to not disturb the engine
+    pub(crate) fn record(&self, direction: Direction, line: &str) {
+        let elapsed = self.start.elapsed().as_secs_f64();
+        let mut sink = self.sink.lock().expect("couldn't acquire trace lock");
+        let _ = writeln!(sink, "{:>12.6} {} {}", elapsed, direction, line);
+    }
+}
+
+impl std::fmt::Debug for Trace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Trace")
+            .field("start", &self.start)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::trace::{Direction, Trace};
+    use std::sync::{Arc, Mutex};
+
+    /// Writer keeping everything written to it, to inspect it after the trace is done
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_trace() {
+        let buffer = Buffer::default();
+        let trace = Trace::new(buffer.clone());
+        trace.record(Direction::Sent, "uci");
+        trace.clone().record(Direction::Received, "uciok");
+        let written = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = written.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].trim_start().starts_with("0.0"));
+        assert!(lines[0].ends_with(" > uci"));
+        assert!(lines[1].ends_with(" < uciok"));
+    }
+}
@@ -0,0 +1,162 @@
+use crate::parse::Wdl;
+use crate::score::Score;
+
+/// Stockfish 16.1 coefficients of the polynomials for the center and spread of its logistic
+/// model, evaluated on the material left on the board
+const STOCKFISH_AS: [f64; 4] = [-1.06249702, 7.42016937, 0.89425629, 348.60356174];
+const STOCKFISH_BS: [f64; 4] = [-5.33122190, 39.57831533, -90.84473771, 123.40620748];
+
+/// Material the Stockfish model is evaluated with when the material on the board is unknown
+const STOCKFISH_MATERIAL: u32 = 58;
+
+/// Lichess's slope for converting centipawns to winning chances
+const LICHESS_SLOPE: f64 = -0.00368208;
+
+/// Lichess's limit for centipawn scores before converting them
+const LICHESS_MAX_CP: i32 = 1000;
+
+/// Model estimating the win, draw and loss expectation of a score, for engines that don't
+/// report it. Models taking the game ply as input, like the ones of Stockfish before 16, aren't
+/// supported.
+///
+/// ```
+/// use async_uci::score::Score;
+/// use async_uci::wdl::WdlModel;
+///
+/// let wdl = WdlModel::Stockfish.wdl(Score::Centipawns(100), None);
+/// assert_eq!(wdl.win, 500);
+/// assert_eq!(WdlModel::Lichess.wdl(Score::Centipawns(0), None).win, 500);
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum WdlModel {
+    /// Stockfish 16.1 model, fitted on its self-play games. Scores are normalized so 100
+    /// centipawns is a 50% win chance with any material, the material left on the board (pawn
+    /// 1, knight and bishop 3, rook 5, queen 9) widens the spread of the curve, other scores are
+    /// less decisive in endgames.
+    #[default]
+    Stockfish,
+    /// Lichess's winning chances. It has no draws, the expected score is split between win and
+    /// loss, and mates are converted to large centipawn scores.
+    Lichess,
+    /// Logistic curve with a fixed center `a` and spread `b` in centipawns, the win chance for
+    /// a score is `1 / (1 + e^((a - cp) / b))` and the loss chance is the win chance of `-cp`
+    Logistic { a: f64, b: f64 },
+}
+
+impl WdlModel {
+    /// Estimate the expectation in permille of a score, from the point of view of the side the
+    /// score is for. The material only applies to the Stockfish model, when it's unknown the
+    /// model is evaluated with 58, the reference material of Stockfish.
+    pub fn wdl(&self, score: Score, material: Option<u32>) -> Wdl {
+        match (self, score) {
+            (WdlModel::Lichess, score) => lichess_wdl(score),
+            (_, Score::Mate(moves)) if moves > 0 => Wdl {
+                win: 1000,
+                draw: 0,
+                loss: 0,
+            },
+            (_, Score::Mate(_)) => Wdl {
+                win: 0,
+                draw: 0,
+                loss: 1000,
+            },
+            (WdlModel::Stockfish, Score::Centipawns(cp)) => {
+                let m = material.unwrap_or(STOCKFISH_MATERIAL).clamp(17, 78) as f64 / 58.0;
+                let a = polynomial(&STOCKFISH_AS, m);
+                let b = polynomial(&STOCKFISH_BS, m);
+                // the model works on Stockfish's internal values, which are reported as
+                // centipawns scaled by the center of the curve for the material on the board
+                let value = |cp: i32| cp as f64 * a / 100.0;
+                from_chances(logistic(a, b, value(cp)), logistic(a, b, value(-cp)))
+            }
+            (WdlModel::Logistic { a, b }, Score::Centipawns(cp)) => {
+                from_chances(logistic(*a, *b, cp as f64), logistic(*a, *b, -cp as f64))
+            }
+        }
+    }
+}
+
+/// Evaluate a cubic polynomial with the coefficients given from the highest degree
+fn polynomial(coefficients: &[f64; 4], x: f64) -> f64 {
+    coefficients.iter().fold(0.0, |acc, c| acc * x + c)
+}
+
+/// Chance of winning with the given value, for a curve centered in `a` with spread `b`
+fn logistic(a: f64, b: f64, value: f64) -> f64 {
+    1.0 / (1.0 + ((a - value) / b).exp())
+}
+
+/// Build the expectation in permille from win and loss chances, draws are the rest
+fn from_chances(win: f64, loss: f64) -> Wdl {
+    let win = (win * 1000.0).round() as isize;
+    let loss = (loss * 1000.0).round() as isize;
+    Wdl {
+        win,
+        draw: 1000 - win - loss,
+        loss,
+    }
+}
+
+/// Lichess's winning chances, with mates counted as big centipawn advantages
+fn lichess_wdl(score: Score) -> Wdl {
+    let cp = match score {
+        Score::Centipawns(cp) => cp.clamp(-LICHESS_MAX_CP, LICHESS_MAX_CP),
+        Score::Mate(moves) if moves > 0 => (21 - moves.min(10)) * 100,
+        Score::Mate(moves) => -(21 - moves.abs().min(10)) * 100,
+    };
+    let expected = 1.0 / (1.0 + (LICHESS_SLOPE * cp as f64).exp());
+    let win = (expected * 1000.0).round() as isize;
+    Wdl {
+        win,
+        draw: 0,
+        loss: 1000 - win,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::parse::Wdl;
+    use crate::score::Score;
+    use crate::wdl::WdlModel;
+
+    #[test]
+    fn test_wdl_models() {
+        let even = WdlModel::Stockfish.wdl(Score::Centipawns(0), None);
+        assert_eq!(even.win, even.loss);
+        assert!(even.draw > 900);
+        let ahead = WdlModel::Stockfish.wdl(Score::Centipawns(100), None);
+        assert_eq!(ahead.win, 500);
+        assert_eq!(ahead.win + ahead.draw + ahead.loss, 1000);
+        // a pawn up is a 50% win with any material
+        for material in [20, 78] {
+            let wdl = WdlModel::Stockfish.wdl(Score::Centipawns(100), Some(material));
+            assert_eq!(wdl.win, 500);
+        }
+        // other advantages are less decisive with less material on the board
+        let middlegame = WdlModel::Stockfish.wdl(Score::Centipawns(200), Some(78));
+        let endgame = WdlModel::Stockfish.wdl(Score::Centipawns(200), Some(20));
+        assert!(endgame.win < middlegame.win && endgame.draw > middlegame.draw);
+        let behind = WdlModel::Stockfish.wdl(Score::Centipawns(-100), None);
+        assert_eq!((behind.win, behind.loss), (ahead.loss, ahead.win));
+        assert_eq!(
+            WdlModel::Stockfish.wdl(Score::Mate(-2), None),
+            Wdl {
+                win: 0,
+                draw: 0,
+                loss: 1000
+            }
+        );
+
+        let lichess = WdlModel::Lichess.wdl(Score::Centipawns(300), None);
+        assert_eq!((lichess.win, lichess.draw, lichess.loss), (751, 0, 249));
+        assert_eq!(
+            WdlModel::Lichess.wdl(Score::Centipawns(5000), None),
+            WdlModel::Lichess.wdl(Score::Centipawns(1000), None)
+        );
+        assert!(WdlModel::Lichess.wdl(Score::Mate(3), None).win > 970);
+
+        let logistic = WdlModel::Logistic { a: 50.0, b: 50.0 };
+        assert_eq!(logistic.wdl(Score::Centipawns(50), None).win, 500);
+        assert_eq!(logistic.wdl(Score::Centipawns(-50), None).loss, 500);
+    }
+}
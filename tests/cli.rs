@@ -0,0 +1,50 @@
+/// Run a search with the CLI, press Ctrl-C once the first evaluation is printed and return the
+/// lines printed after it
+#[cfg(unix)]
+fn interrupt_search(args: &[&str]) -> anyhow::Result<Vec<String>> {
+    use std::io::{BufRead, BufReader};
+    use std::os::unix::process::CommandExt;
+    use std::process::{Command, Stdio};
+
+    let engine = concat!(env!("CARGO_MANIFEST_DIR"), "/res/test/fakefish.sh");
+    // the CLI gets a group of its own, as a shell does for the foreground job
+    let mut cli = Command::new(env!("CARGO_BIN_EXE_async-uci"))
+        .args(["-P", engine, "search"])
+        .args(args)
+        .stdout(Stdio::piped())
+        .process_group(0)
+        .spawn()?;
+    let mut lines = BufReader::new(cli.stdout.take().expect("no stdout")).lines();
+    // the search is running once the first evaluation is printed
+    while !lines.next().expect("CLI exited")?.starts_with("score: ") {}
+    // Ctrl-C in a terminal interrupts every process in the foreground group
+    assert_eq!(unsafe { libc::kill(-(cli.id() as i32), libc::SIGINT) }, 0);
+    let output: Vec<String> = lines.collect::<Result<_, _>>()?;
+    assert!(cli.wait()?.success());
+    Ok(output)
+}
+
+#[cfg(unix)]
+#[test]
+fn test_search_interrupted() -> anyhow::Result<()> {
+    let fen = "r2qk2r/pp3ppp/B1nbpn2/2pp1b2/Q2P1B2/2P1PN2/PP1N1PPP/R3K2R b KQkq - 4 8";
+    let output = interrupt_search(&["--fen", fen])?;
+    assert_eq!(
+        output.last().map(String::as_str),
+        Some("bestmove: a8b8 ponder: f4d6")
+    );
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn test_search_ponderhit() -> anyhow::Result<()> {
+    let fen = "r2qk2r/pp3ppp/B1nbpn2/2pp1b2/Q2P1B2/2P1PN2/PP1N1PPP/R3K2R b KQkq - 4 8";
+    // Ctrl-C confirms the expected reply, fakefish then answers with the ponder search result
+    let output = interrupt_search(&["--fen", fen, "--moves", "a8b8", "--ponder"])?;
+    assert_eq!(
+        output.last().map(String::as_str),
+        Some("bestmove: d6f4 ponder: e3f4")
+    );
+    Ok(())
+}